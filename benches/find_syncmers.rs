@@ -3,6 +3,7 @@ use pulp::Arch;
 
 use syncmers::*;
 
+#[allow(clippy::if_same_then_else)]
 pub fn find_syncmers_pos_improved<const N: usize>(
     k: usize,
     s: usize,
@@ -35,6 +36,7 @@ pub fn find_syncmers_pos_improved<const N: usize>(
         .collect::<Vec<_>>()
 }
 
+#[allow(clippy::if_same_then_else)]
 pub fn find_syncmers_current<const N: usize>(
     k: usize,
     s: usize,
@@ -196,7 +198,7 @@ pub fn find_syncmers_anonfn_pulp(k: usize, s: usize, ts: &[usize], seq: &[u8]) -
 pub fn find_syncmers_anonfn_pulp_u8(k: usize, s: usize, ts: &[u8], seq: &[u8]) -> Vec<usize> {
     assert!(seq.len() > k);
     assert!(s < k);
-    assert!(ts.iter().all(|&t| t as usize <= k as usize - s as usize));
+    assert!(ts.iter().all(|&t| t as usize <= k - s));
     assert!(
         ts.len() < 5,
         "Only supports up to 4 syncmers. Email if you'd like more (or change the lines)"
@@ -233,7 +235,7 @@ pub fn find_syncmers_anonfn_pulp_u8(k: usize, s: usize, ts: &[u8], seq: &[u8]) -
 pub fn find_syncmers_anonfn_u8(k: usize, s: usize, ts: &[u8], seq: &[u8]) -> Vec<usize> {
     assert!(seq.len() > k);
     assert!(s < k);
-    assert!(ts.iter().all(|&t| t as usize <= k as usize - s as usize));
+    assert!(ts.iter().all(|&t| t as usize <= k - s));
     assert!(
         ts.len() < 5,
         "Only supports up to 4 syncmers. Email if you'd like more (or change the lines)"
@@ -270,7 +272,7 @@ pub fn find_syncmers_anonfn_u8(k: usize, s: usize, ts: &[u8], seq: &[u8]) -> Vec
 pub fn find_syncmers_anonfn_u8_contains(k: usize, s: usize, ts: &[u8], seq: &[u8]) -> Vec<usize> {
     assert!(seq.len() > k);
     assert!(s < k);
-    assert!(ts.iter().all(|&t| t as usize <= k as usize - s as usize));
+    assert!(ts.iter().all(|&t| t as usize <= k - s));
     assert!(
         ts.len() < 5,
         "Only supports up to 4 syncmers. Email if you'd like more (or change the lines)"
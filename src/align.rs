@@ -0,0 +1,302 @@
+//! Base-level alignment of chains: anchors are taken as exact matches and the gaps between
+//! consecutive anchors are filled with banded global alignment (unit-cost edit distance)
+//!
+//! Only the span from the first to the last anchor of a chain is aligned; there is no
+//! extension past the chain ends.
+
+use std::fmt::Write;
+
+use crate::chain::{Anchor, Chain};
+use crate::{revcomp, Strand};
+
+/// CIGAR operation, with `=`/`X` distinguishing matches from mismatches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CigarOp {
+    Match,
+    Mismatch,
+    /// Query bases missing from the reference
+    Insertion,
+    /// Reference bases missing from the query
+    Deletion,
+}
+
+impl CigarOp {
+    pub fn to_char(self) -> char {
+        match self {
+            CigarOp::Match => '=',
+            CigarOp::Mismatch => 'X',
+            CigarOp::Insertion => 'I',
+            CigarOp::Deletion => 'D',
+        }
+    }
+}
+
+/// Alignment of a chain. Coordinates are half-open on the forward strands; for Reverse chains
+/// the CIGAR runs along the reverse complement of the query, as in SAM and PAF
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alignment {
+    pub strand: Strand,
+    pub query_start: usize,
+    pub query_end: usize,
+    pub ref_start: usize,
+    pub ref_end: usize,
+    pub cigar: Vec<(usize, CigarOp)>,
+}
+
+impl Alignment {
+    /// Total length of the operations of one kind
+    fn count(&self, op: CigarOp) -> usize {
+        self.cigar.iter().filter(|x| x.1 == op).map(|x| x.0).sum()
+    }
+
+    pub fn matches(&self) -> usize {
+        self.count(CigarOp::Match)
+    }
+
+    /// Number of columns (matches, mismatches and gaps)
+    pub fn len(&self) -> usize {
+        self.cigar.iter().map(|x| x.0).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cigar.is_empty()
+    }
+
+    pub fn edit_distance(&self) -> usize {
+        self.len() - self.matches()
+    }
+
+    /// Matches over alignment columns (BLAST identity)
+    pub fn identity(&self) -> f64 {
+        if self.is_empty() {
+            0.0
+        } else {
+            self.matches() as f64 / self.len() as f64
+        }
+    }
+
+    /// e.g. "10=1X5=2I3="
+    pub fn cigar_string(&self) -> String {
+        let mut out = String::new();
+        for (n, op) in &self.cigar {
+            write!(out, "{}{}", n, op.to_char()).unwrap();
+        }
+        out
+    }
+}
+
+/// Append n ops, merging with the last run
+fn push(cigar: &mut Vec<(usize, CigarOp)>, n: usize, op: CigarOp) {
+    match cigar.last_mut() {
+        _ if n == 0 => {}
+        Some(last) if last.1 == op => last.0 += n,
+        _ => cigar.push((n, op)),
+    }
+}
+
+/// Global edit-distance alignment of query against reference, restricted to diagonals within
+/// `band` of the band between the two corners
+pub fn banded_align(query: &[u8], reference: &[u8], band: usize) -> Vec<(usize, CigarOp)> {
+    let (n, m) = (query.len() as isize, reference.len() as isize);
+    // Cell (i, j) is in the band when lo <= j - i <= hi
+    let lo = (m - n).min(0) - band as isize;
+    let hi = (m - n).max(0) + band as isize;
+    let width = (hi - lo + 1) as usize;
+    let at = |i: isize, j: isize| i as usize * width + (j - i - lo) as usize;
+    let in_band = |i: isize, j: isize| j >= 0 && j <= m && j - i >= lo && j - i <= hi;
+
+    let mut costs = vec![usize::MAX; (n as usize + 1) * width];
+    let mut ops = vec![CigarOp::Match; (n as usize + 1) * width];
+    for i in 0..=n {
+        for j in (i + lo).max(0)..=(i + hi).min(m) {
+            let mut best = (usize::MAX, CigarOp::Match);
+            if i == 0 && j == 0 {
+                best.0 = 0;
+            }
+            if i > 0 && j > 0 {
+                let op = if query[i as usize - 1] == reference[j as usize - 1] {
+                    CigarOp::Match
+                } else {
+                    CigarOp::Mismatch
+                };
+                let cost = costs[at(i - 1, j - 1)] + (op == CigarOp::Mismatch) as usize;
+                if cost < best.0 {
+                    best = (cost, op);
+                }
+            }
+            if i > 0 && in_band(i - 1, j) && costs[at(i - 1, j)] + 1 < best.0 {
+                best = (costs[at(i - 1, j)] + 1, CigarOp::Insertion);
+            }
+            if j > 0 && in_band(i, j - 1) && costs[at(i, j - 1)] + 1 < best.0 {
+                best = (costs[at(i, j - 1)] + 1, CigarOp::Deletion);
+            }
+            costs[at(i, j)] = best.0;
+            ops[at(i, j)] = best.1;
+        }
+    }
+
+    let mut reversed = Vec::new();
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        let op = ops[at(i, j)];
+        push(&mut reversed, 1, op);
+        match op {
+            CigarOp::Match | CigarOp::Mismatch => {
+                i -= 1;
+                j -= 1;
+            }
+            CigarOp::Insertion => i -= 1,
+            CigarOp::Deletion => j -= 1,
+        }
+    }
+    reversed.reverse();
+    reversed
+}
+
+/// Align a chain of query against reference, filling the gaps between anchors with
+/// [`banded_align`]
+pub fn align_chain(chain: &Chain, query: &[u8], reference: &[u8], band: usize) -> Alignment {
+    // Work along the query strand the chain runs on
+    let mut oriented = query.to_vec();
+    let anchor_start = |x: &Anchor| match chain.strand {
+        Strand::Forward => x.query_pos,
+        Strand::Reverse => query.len() - x.query_pos - x.k,
+    };
+    if chain.strand == Strand::Reverse {
+        revcomp(&mut oriented);
+    }
+
+    let first = &chain.anchors[0];
+    let (mut q, mut r) = (anchor_start(first), first.ref_pos);
+    let (q_start, r_start) = (q, r);
+    let mut cigar = Vec::new();
+    for anchor in &chain.anchors {
+        let (qs, rs) = (anchor_start(anchor), anchor.ref_pos);
+        let (qe, re) = (qs + anchor.k, rs + anchor.k);
+        if qs as isize - rs as isize == q as isize - r as isize && qs <= q {
+            // Overlaps the previous anchor on the same diagonal: extends the match
+            if qe > q {
+                push(&mut cigar, qe - q, CigarOp::Match);
+                (q, r) = (qe, re);
+            }
+        } else if qs >= q && rs >= r {
+            cigar.extend(banded_align(&oriented[q..qs], &reference[r..rs], band));
+            push(&mut cigar, anchor.k, CigarOp::Match);
+            (q, r) = (qe, re);
+        }
+        // Anchors crossing the previous one on another diagonal are skipped
+    }
+
+    // Anchors are hash matches: recount columns taken as matches against the bases
+    let mut checked = Vec::new();
+    let (mut i, mut j) = (q_start, r_start);
+    for &(n, op) in &cigar {
+        match op {
+            CigarOp::Match | CigarOp::Mismatch => {
+                for _ in 0..n {
+                    let op = if oriented[i] == reference[j] {
+                        CigarOp::Match
+                    } else {
+                        CigarOp::Mismatch
+                    };
+                    push(&mut checked, 1, op);
+                    i += 1;
+                    j += 1;
+                }
+            }
+            CigarOp::Insertion => {
+                push(&mut checked, n, op);
+                i += n;
+            }
+            CigarOp::Deletion => {
+                push(&mut checked, n, op);
+                j += n;
+            }
+        }
+    }
+
+    let (query_start, query_end) = match chain.strand {
+        Strand::Forward => (q_start, q),
+        Strand::Reverse => (query.len() - q, query.len() - q_start),
+    };
+    Alignment {
+        strand: chain.strand,
+        query_start,
+        query_end,
+        ref_start: r_start,
+        ref_end: r,
+        cigar: checked,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chain::{chain, match_syncmers, ChainParams};
+    use crate::find_syncmers_pos;
+    use crate::test_util::random_seq;
+
+    #[test]
+    pub fn test_banded_align() {
+        let cigar = |q: &[u8], r: &[u8]| Alignment {
+            strand: Strand::Forward,
+            query_start: 0,
+            query_end: q.len(),
+            ref_start: 0,
+            ref_end: r.len(),
+            cigar: banded_align(q, r, 4),
+        };
+        assert_eq!(cigar(b"ACGTACGT", b"ACGTACGT").cigar_string(), "8=");
+        assert_eq!(cigar(b"ACGTTACGT", b"ACGTACGT").edit_distance(), 1);
+        assert_eq!(cigar(b"ACGAACGT", b"ACGTACGT").cigar_string(), "3=1X4=");
+        assert_eq!(cigar(b"", b"ACG").cigar_string(), "3D");
+        let x = cigar(b"AACCGGTTAACCGGTT", b"AACCGGAACCGGTT");
+        assert_eq!((x.edit_distance(), x.identity()), (2, 14.0 / 16.0));
+    }
+
+    #[test]
+    pub fn test_align_chain() {
+        let reference = random_seq(10_000, 29);
+        let mut query = reference[2_000..5_000].to_vec();
+        for i in (100..2_900).step_by(150) {
+            query[i] = if query[i] == b'A' { b'C' } else { b'A' };
+        }
+        query.drain(1_000..1_004);
+        query.splice(2_000..2_000, b"GATTACA".iter().copied());
+
+        for reverse in [false, true] {
+            let mut query = query.clone();
+            if reverse {
+                revcomp(&mut query);
+            }
+            let ts = [4];
+            let ref_pos = find_syncmers_pos(15, 7, &ts, &reference);
+            let query_pos = find_syncmers_pos(15, 7, &ts, &query);
+            let anchors = match_syncmers(15, &query, &query_pos, &reference, &ref_pos);
+            let best = &chain(&anchors, &ChainParams::default())[0];
+            let alignment = align_chain(best, &query, &reference, 32);
+
+            // The CIGAR spans exactly the aligned ranges
+            let span = |ops: &[CigarOp]| -> usize {
+                alignment
+                    .cigar
+                    .iter()
+                    .filter(|x| ops.contains(&x.1))
+                    .map(|x| x.0)
+                    .sum()
+            };
+            let diagonal = [CigarOp::Match, CigarOp::Mismatch];
+            assert_eq!(
+                span(&[diagonal[0], diagonal[1], CigarOp::Insertion]),
+                alignment.query_end - alignment.query_start
+            );
+            assert_eq!(
+                span(&[diagonal[0], diagonal[1], CigarOp::Deletion]),
+                alignment.ref_end - alignment.ref_start
+            );
+            assert!(alignment.identity() > 0.98 && alignment.identity() < 1.0);
+            assert_eq!(span(&[CigarOp::Deletion]), 4);
+            assert_eq!(span(&[CigarOp::Insertion]), 7);
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! Colinear chaining of syncmer anchors between a query and a reference
+//!
+//! The DP follows minimap2 (Li 2018): an anchor extends the best chain ending at one of the
+//! previous `lookback` anchors, gaining the bases it adds and paying a gap cost for the
+//! difference between the reference and query distances.
+//!
+//! ```rust
+//! # use syncmers::find_syncmers_pos;
+//! # use syncmers::chain::{chain, match_syncmers, ChainParams};
+//! let reference = b"CCAGTGTTTACGGATCGATCGGGATCTAGCTAGCTAGGCTAGCATCGACTTTAGCTAGC".to_vec();
+//! let query = &reference[10..50];
+//! let ts = [2];
+//! let ref_pos = find_syncmers_pos(5, 2, &ts, &reference);
+//! let query_pos = find_syncmers_pos(5, 2, &ts, query);
+//! let anchors = match_syncmers(5, query, &query_pos, &reference, &ref_pos);
+//! let params = ChainParams { min_anchors: 2, min_score: 10.0, ..Default::default() };
+//! let chains = chain(&anchors, &params);
+//! assert_eq!(chains[0].ref_start - chains[0].query_start, 10);
+//! ```
+
+use std::collections::HashMap;
+
+use crate::{revcomp, Strand};
+
+/// A kmer match: the query kmer at query_pos matches the reference kmer at ref_pos, on the
+/// same strand (Forward) or reverse complemented (Reverse). Positions are kmer starts on the
+/// forward strands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Anchor {
+    pub query_pos: usize,
+    pub ref_pos: usize,
+    pub strand: Strand,
+    pub k: usize,
+}
+
+impl Anchor {
+    /// End of the anchor on the query, in the direction the chain runs: forward for Forward
+    /// anchors and along the reverse complement (negated) for Reverse ones
+    #[inline]
+    fn query_end(&self) -> i64 {
+        match self.strand {
+            Strand::Forward => (self.query_pos + self.k) as i64,
+            Strand::Reverse => -(self.query_pos as i64),
+        }
+    }
+
+    #[inline]
+    fn ref_end(&self) -> i64 {
+        (self.ref_pos + self.k) as i64
+    }
+}
+
+/// Chaining parameters. The defaults are minimap2's for long reads
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainParams {
+    /// Maximum distance between consecutive anchors on either sequence
+    pub max_gap: usize,
+    /// Maximum difference between the reference and query distances of consecutive anchors
+    pub bandwidth: usize,
+    /// Number of previous anchors tried as predecessors
+    pub lookback: usize,
+    /// Chains with fewer anchors are dropped
+    pub min_anchors: usize,
+    /// Chains scoring less are dropped
+    pub min_score: f64,
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        ChainParams {
+            max_gap: 5000,
+            bandwidth: 500,
+            lookback: 50,
+            min_anchors: 3,
+            min_score: 40.0,
+        }
+    }
+}
+
+/// A colinear chain of anchors with its coordinate ranges (half-open, forward strands)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chain {
+    pub strand: Strand,
+    pub score: f64,
+    pub query_start: usize,
+    pub query_end: usize,
+    pub ref_start: usize,
+    pub ref_end: usize,
+    /// Anchors by increasing reference position
+    pub anchors: Vec<Anchor>,
+}
+
+impl Chain {
+    fn new(strand: Strand, score: f64, anchors: Vec<Anchor>) -> Self {
+        Chain {
+            strand,
+            score,
+            query_start: anchors.iter().map(|x| x.query_pos).min().unwrap(),
+            query_end: anchors.iter().map(|x| x.query_pos + x.k).max().unwrap(),
+            ref_start: anchors.iter().map(|x| x.ref_pos).min().unwrap(),
+            ref_end: anchors.iter().map(|x| x.ref_pos + x.k).max().unwrap(),
+            anchors,
+        }
+    }
+}
+
+/// Anchors between the kmers at query_positions and ref_positions (e.g. from
+/// [`crate::find_syncmers_pos`]) that are identical or reverse complements
+pub fn match_syncmers(
+    k: usize,
+    query: &[u8],
+    query_positions: &[usize],
+    reference: &[u8],
+    ref_positions: &[usize],
+) -> Vec<Anchor> {
+    let mut kmers: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for &pos in ref_positions {
+        kmers.entry(&reference[pos..pos + k]).or_default().push(pos);
+    }
+
+    let mut anchors = Vec::new();
+    let mut rc = Vec::with_capacity(k);
+    for &query_pos in query_positions {
+        let kmer = &query[query_pos..query_pos + k];
+        rc.clear();
+        rc.extend_from_slice(kmer);
+        revcomp(&mut rc);
+        for (kmer, strand) in [(kmer, Strand::Forward), (&rc[..], Strand::Reverse)] {
+            for &ref_pos in kmers.get(kmer).into_iter().flatten() {
+                anchors.push(Anchor {
+                    query_pos,
+                    ref_pos,
+                    strand,
+                    k,
+                });
+            }
+        }
+    }
+    anchors
+}
+
+/// minimap2's gap cost for a gap of l bases between anchors of length k
+#[inline]
+fn gap_cost(l: i64, k: usize) -> f64 {
+    if l == 0 {
+        0.0
+    } else {
+        0.01 * k as f64 * l as f64 + 0.5 * (l as f64).log2()
+    }
+}
+
+/// Chain anchors on both strands, returning chains by decreasing score. Each anchor is used
+/// by at most one chain
+pub fn chain(anchors: &[Anchor], params: &ChainParams) -> Vec<Chain> {
+    let mut chains = Vec::new();
+    for strand in [Strand::Forward, Strand::Reverse] {
+        let mut strand_anchors = anchors
+            .iter()
+            .filter(|x| x.strand == strand)
+            .copied()
+            .collect::<Vec<_>>();
+        strand_anchors.sort_unstable_by_key(|x| (x.ref_end(), x.query_end()));
+        strand_anchors.dedup();
+        chain_strand(strand, &strand_anchors, params, &mut chains);
+    }
+    chains.sort_by(|a, b| b.score.total_cmp(&a.score));
+    chains
+}
+
+/// DP over anchors of one strand, sorted by reference end
+fn chain_strand(strand: Strand, anchors: &[Anchor], params: &ChainParams, out: &mut Vec<Chain>) {
+    let n = anchors.len();
+    let mut scores = vec![0.0; n];
+    let mut predecessors = vec![None; n];
+
+    for i in 0..n {
+        let (ri, qi) = (anchors[i].ref_end(), anchors[i].query_end());
+        let k = anchors[i].k;
+        scores[i] = k as f64;
+
+        for j in (i.saturating_sub(params.lookback)..i).rev() {
+            let dr = ri - anchors[j].ref_end();
+            let dq = qi - anchors[j].query_end();
+            if dr > params.max_gap as i64 {
+                break;
+            }
+            if dr <= 0 || dq <= 0 || dq > params.max_gap as i64 {
+                continue;
+            }
+            let gap = (dr - dq).abs();
+            if gap > params.bandwidth as i64 {
+                continue;
+            }
+            let score = scores[j] + dr.min(dq).min(k as i64) as f64 - gap_cost(gap, k);
+            if score > scores[i] {
+                scores[i] = score;
+                predecessors[i] = Some(j);
+            }
+        }
+    }
+
+    // Backtrack from the best ends, stopping at anchors already taken by a better chain
+    let mut order = (0..n).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+    let mut used = vec![false; n];
+    for end in order {
+        if used[end] {
+            continue;
+        }
+        let mut members = Vec::new();
+        let mut score = scores[end];
+        let mut i = Some(end);
+        while let Some(x) = i {
+            if used[x] {
+                score -= scores[x];
+                break;
+            }
+            used[x] = true;
+            members.push(anchors[x]);
+            i = predecessors[x];
+        }
+        if members.len() >= params.min_anchors && score >= params.min_score {
+            members.reverse();
+            out.push(Chain::new(strand, score, members));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::find_syncmers_pos;
+    use crate::test_util::random_seq;
+
+    #[test]
+    pub fn test_chain_with_mutations() {
+        let reference = random_seq(20_000, 11);
+        let mut query = reference[5_000..8_000].to_vec();
+        for i in (50..3_000).step_by(97) {
+            query[i] = if query[i] == b'A' { b'C' } else { b'A' };
+        }
+        // A 30 base deletion in the query
+        query.drain(1_500..1_530);
+
+        let ts = [5];
+        let ref_pos = find_syncmers_pos(15, 5, &ts, &reference);
+        let query_pos = find_syncmers_pos(15, 5, &ts, &query);
+        let anchors = match_syncmers(15, &query, &query_pos, &reference, &ref_pos);
+        let chains = chain(&anchors, &ChainParams::default());
+
+        let best = &chains[0];
+        assert_eq!(best.strand, Strand::Forward);
+        assert!(best.query_start < 100 && best.query_end > query.len() - 100);
+        assert!(best.ref_start < 5_100 && best.ref_end > 7_900);
+        // Spurious hits elsewhere only make tiny chains, if any
+        assert!(chains[1..].iter().all(|x| x.score < best.score / 10.0));
+        for x in &best.anchors {
+            let offset = if x.query_pos < 1_500 { 5_000 } else { 5_030 };
+            assert_eq!(x.ref_pos, x.query_pos + offset);
+        }
+
+        let mut rc = query.clone();
+        revcomp(&mut rc);
+        let rc_pos = find_syncmers_pos(15, 5, &ts, &rc);
+        let anchors = match_syncmers(15, &rc, &rc_pos, &reference, &ref_pos);
+        let chains = chain(&anchors, &ChainParams::default());
+        // Only kmers that are syncmers on both strands match, so the chain is sparser
+        assert_eq!(chains[0].strand, Strand::Reverse);
+        assert!(chains[0].ref_start < 5_200 && chains[0].ref_end > 7_800);
+        assert!(chains[0]
+            .anchors
+            .iter()
+            .all(|x| x.ref_pos >= best.ref_start));
+    }
+
+    #[test]
+    pub fn test_chain_respects_max_gap() {
+        let anchor = |query_pos, ref_pos| Anchor {
+            query_pos,
+            ref_pos,
+            strand: Strand::Forward,
+            k: 15,
+        };
+        let anchors = [
+            anchor(0, 100),
+            anchor(20, 120),
+            anchor(40, 140),
+            anchor(60, 10_140),
+            anchor(80, 10_160),
+            anchor(100, 10_180),
+        ];
+        let chains = chain(&anchors, &ChainParams::default());
+        assert_eq!(chains.len(), 2);
+        assert_eq!(chains[0].anchors.len(), 3);
+        assert_eq!(chains[0].score, 45.0);
+    }
+}
@@ -0,0 +1,158 @@
+//! Content-defined chunking at syncmer positions, for deduplicating similar sequences
+//!
+//! Whether a position is a syncmer depends only on the k bases there, so an edit only moves
+//! the chunk boundaries near it: chunks resynchronize at the first syncmer past the edit that
+//! is at least min_size from the previous boundary. Boundaries are not strand-independent,
+//! even with canonical params: a chunk starts where a syncmer starts, which on the reverse
+//! complement is k bases away, and the min_size scan runs in the direction of the sequence.
+//! Chunks forced at max_size (in sequences without syncmers, e.g. runs of N) are not
+//! content-defined, so max_size should be well above the typical distance between syncmers.
+
+use crate::{Error, Params};
+
+/// A chunk of a sequence, with the hash of its bases
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chunk {
+    pub offset: usize,
+    pub len: usize,
+    pub hash: u64,
+}
+
+/// Splits sequences into chunks starting at syncmers
+///
+/// ```rust
+/// # use syncmers::Params;
+/// # use syncmers::chunk::Chunker;
+/// let chunker = Chunker::new(Params::new(5, 2, &[2]).unwrap(), 4, 16).unwrap();
+/// let seq = b"CCAGTGTTTACGGCCAGTGTTTACGGCCAGTGTTTACGG";
+/// let chunks = chunker.chunks(seq);
+/// assert_eq!(chunks.iter().map(|x| x.len).sum::<usize>(), seq.len());
+/// assert!(chunks.iter().all(|x| x.len <= 16));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunker {
+    params: Params,
+    min_size: usize,
+    max_size: usize,
+}
+
+impl Chunker {
+    pub fn new(params: Params, min_size: usize, max_size: usize) -> Result<Self, Error> {
+        if min_size == 0 || min_size > max_size {
+            return Err(Error::InvalidParams(format!(
+                "Chunk sizes must satisfy 0 < min_size <= max_size, got {} and {}",
+                min_size, max_size
+            )));
+        }
+        Ok(Chunker {
+            params,
+            min_size,
+            max_size,
+        })
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn min_size(&self) -> usize {
+        self.min_size
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Split seq into consecutive chunks covering all of it. Only the last chunk may be
+    /// shorter than min_size
+    pub fn chunks(&self, seq: &[u8]) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+        let mut push = |offset: usize, len: usize| {
+            chunks.push(Chunk {
+                offset,
+                len,
+                hash: self.params.hash(&seq[offset..offset + len]),
+            });
+        };
+
+        let mut start = 0;
+        self.params.for_each_syncmer(seq, |x| {
+            while x.pos - start > self.max_size {
+                push(start, self.max_size);
+                start += self.max_size;
+            }
+            if x.pos - start >= self.min_size {
+                push(start, x.pos - start);
+                start = x.pos;
+            }
+        });
+        while seq.len() - start > self.max_size {
+            push(start, self.max_size);
+            start += self.max_size;
+        }
+        if start < seq.len() {
+            push(start, seq.len() - start);
+        }
+        chunks
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::random_seq;
+    use std::collections::HashSet;
+
+    #[test]
+    pub fn test_chunk_sizes() {
+        let params = Params::new(21, 11, &[5]).unwrap().with_canonical(true);
+        let chunker = Chunker::new(params.clone(), 64, 256).unwrap();
+        let mut seq = random_seq(50_000, 71);
+        // No syncmers in the Ns: forced chunks
+        seq[20_000..21_000].fill(b'N');
+        let chunks = chunker.chunks(&seq);
+
+        let mut offset = 0;
+        for chunk in &chunks {
+            assert_eq!(chunk.offset, offset);
+            assert!(chunk.len <= 256);
+            assert_eq!(chunk.hash, params.hash(&seq[offset..offset + chunk.len]));
+            offset += chunk.len;
+        }
+        assert_eq!(offset, seq.len());
+        assert!(chunks[..chunks.len() - 1].iter().all(|x| x.len >= 64));
+        assert!(chunks.iter().filter(|x| x.len == 256).count() >= 3);
+
+        assert!(Chunker::new(params.clone(), 0, 10).is_err());
+        assert!(Chunker::new(params.clone(), 20, 10).is_err());
+        assert!(chunker.chunks(b"").is_empty());
+        assert_eq!(chunker.chunks(&seq[..10]).len(), 1);
+    }
+
+    #[test]
+    pub fn test_chunk_locality() {
+        let params = Params::new(21, 11, &[5]).unwrap().with_canonical(true);
+        let chunker = Chunker::new(params, 64, 4_096).unwrap();
+        let seq = random_seq(100_000, 73);
+        let hashes = |seq: &[u8]| {
+            chunker
+                .chunks(seq)
+                .iter()
+                .map(|x| x.hash)
+                .collect::<HashSet<_>>()
+        };
+        let original = hashes(&seq);
+        assert!(original.len() > 500);
+
+        // A substitution and an insertion each invalidate a few chunks
+        let mut edited = seq.clone();
+        edited[50_000] = if edited[50_000] == b'A' { b'C' } else { b'A' };
+        edited.splice(70_000..70_000, b"GATTACA".iter().copied());
+        let changed = hashes(&edited).difference(&original).count();
+        assert!((2..=6).contains(&changed), "{} chunks changed", changed);
+
+        // Chunks after a new prefix are found again from the first boundary on
+        let prefixed = hashes(&[random_seq(1_000, 79), seq].concat());
+        assert!(original.difference(&prefixed).count() <= 1);
+    }
+}
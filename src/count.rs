@@ -0,0 +1,680 @@
+//! Syncmer counting: like kmer counting, but only syncmers are kept, cutting memory by about
+//! 1/density
+//!
+//! Syncmers are keyed by their canonical kmer packed in 2 bits per base (so k <= 32), and
+//! counted by sort-and-count: each batch of sequence is extracted in parallel, sorted and
+//! run-length counted, then merged into the running counts. Kmers with bases other than ACGT
+//! are skipped. Counts are written as jellyfish-style histograms and dumps.
+//!
+//! When the syncmers don't fit in memory, [`DiskCounter`] spills them to bucket files
+//! partitioned by hash, then sorts and counts one bucket at a time, as KMC does.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+
+use crate::fasta::{FastaReader, Record};
+use crate::{Error, Params, Strand};
+
+/// Largest k that packs into a u64
+pub const MAX_PACKED_K: usize = 32;
+
+/// Bases of sequence extracted and sorted together
+pub const COUNT_BATCH_BASES: usize = 64 * 1024 * 1024;
+
+/// Number of bucket files a [`DiskCounter`] spills to, and most parts a bucket is split into
+pub const DEFAULT_BUCKETS: usize = 512;
+
+/// Bytes held per spilled syncmer occurrence while counting a bucket: 8 to load and sort it,
+/// and up to 16 for its (u64, u32) count entry
+const BYTES_PER_OCCURRENCE: usize = 24;
+
+#[inline]
+pub(crate) fn encode(base: u8) -> Option<u64> {
+    match base {
+        b'A' | b'a' => Some(0),
+        b'C' | b'c' => Some(1),
+        b'G' | b'g' => Some(2),
+        b'T' | b't' => Some(3),
+        _ => None,
+    }
+}
+
+/// Pack a kmer (k <= 32) in 2 bits per base, first base highest, so packed order is
+/// lexicographic. None if it has bases other than ACGT
+pub fn pack(kmer: &[u8]) -> Option<u64> {
+    assert!(kmer.len() <= MAX_PACKED_K);
+    kmer.iter()
+        .try_fold(0u64, |packed, &base| Some(packed << 2 | encode(base)?))
+}
+
+pub fn unpack(packed: u64, k: usize) -> Vec<u8> {
+    (0..k)
+        .map(|i| b"ACGT"[(packed >> (2 * (k - 1 - i)) & 3) as usize])
+        .collect()
+}
+
+/// Packed canonical syncmers of seq, in order of position
+pub(crate) fn packed_syncmers(params: &Params, seq: &[u8], out: &mut Vec<u64>) {
+    let k = params.k();
+    params.for_each_syncmer(seq, |syncmer| {
+        let kmer = &seq[syncmer.pos..syncmer.pos + k];
+        if let Some(packed) = pack(kmer) {
+            out.push(match syncmer.strand {
+                Strand::Forward => packed,
+                Strand::Reverse => revcomp_packed(packed, k),
+            });
+        }
+    });
+}
+
+#[inline]
+pub(crate) fn revcomp_packed(packed: u64, k: usize) -> u64 {
+    // Complement is 3 - x, and reversing the 2-bit groups reverses the kmer
+    let mut x = !packed;
+    x = (x >> 2 & 0x3333_3333_3333_3333) | (x & 0x3333_3333_3333_3333) << 2;
+    x = (x >> 4 & 0x0F0F_0F0F_0F0F_0F0F) | (x & 0x0F0F_0F0F_0F0F_0F0F) << 4;
+    x = x.swap_bytes();
+    x >> (64 - 2 * k)
+}
+
+/// Sort packed syncmers and count runs
+pub(crate) fn sort_and_count(mut packed: Vec<u64>) -> Vec<(u64, u32)> {
+    packed.par_sort_unstable();
+    let mut counts: Vec<(u64, u32)> = Vec::new();
+    for x in packed {
+        match counts.last_mut() {
+            Some(last) if last.0 == x => last.1 = last.1.saturating_add(1),
+            _ => counts.push((x, 1)),
+        }
+    }
+    counts
+}
+
+/// Merge two sorted count lists, adding the counts of shared kmers
+pub(crate) fn merge_counts(a: &[(u64, u32)], b: &[(u64, u32)]) -> Vec<(u64, u32)> {
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].0.cmp(&b[j].0) {
+            std::cmp::Ordering::Less => {
+                out.push(a[i]);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                out.push(b[j]);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                out.push((a[i].0, a[i].1.saturating_add(b[j].1)));
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out.extend_from_slice(&a[i..]);
+    out.extend_from_slice(&b[j..]);
+    out
+}
+
+pub(crate) fn check_count_params(params: &Params) -> Result<(), Error> {
+    if params.k() > MAX_PACKED_K {
+        return Err(Error::InvalidParams(format!(
+            "k must be at most {} for counting, got {}",
+            MAX_PACKED_K,
+            params.k()
+        )));
+    }
+    Ok(())
+}
+
+/// Accumulates syncmer counts over batches of records
+pub struct SyncmerCounter {
+    params: Params,
+    counts: Vec<(u64, u32)>,
+}
+
+impl SyncmerCounter {
+    /// Syncmers are always canonical, so both strands of a read count the same kmer
+    pub fn new(params: Params) -> Result<Self, Error> {
+        check_count_params(&params)?;
+        Ok(SyncmerCounter {
+            params: params.with_canonical(true),
+            counts: Vec::new(),
+        })
+    }
+
+    /// Count the syncmers of a batch of records, in parallel
+    pub fn add_records(&mut self, records: &[Record]) {
+        let batch = sort_and_count(extract(&self.params, records));
+        self.counts = merge_counts(&self.counts, &batch);
+    }
+
+    /// Count every record of a FASTA/FASTQ file, in batches of about [`COUNT_BATCH_BASES`]
+    pub fn add_fasta<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        for_each_batch(path, COUNT_BATCH_BASES, |batch| {
+            self.add_records(batch);
+            Ok(())
+        })
+    }
+
+    pub fn finish(self) -> Counts {
+        Counts {
+            params: self.params,
+            counts: self.counts,
+        }
+    }
+}
+
+/// Call f on batches of records of about batch_bases
+pub(crate) fn for_each_batch<P, F>(path: P, batch_bases: usize, mut f: F) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    F: FnMut(&[Record]) -> Result<(), Error>,
+{
+    let mut batch = Vec::new();
+    let mut bases = 0;
+    for record in FastaReader::from_path(path)? {
+        let record = record?;
+        bases += record.seq.len();
+        batch.push(record);
+        if bases >= batch_bases {
+            f(&batch)?;
+            batch.clear();
+            bases = 0;
+        }
+    }
+    if !batch.is_empty() {
+        f(&batch)?;
+    }
+    Ok(())
+}
+
+/// Packed syncmers extracted from a batch of records, in parallel
+fn extract(params: &Params, records: &[Record]) -> Vec<u64> {
+    records
+        .par_iter()
+        .flat_map_iter(|x| {
+            let mut packed = Vec::new();
+            packed_syncmers(params, &x.seq, &mut packed);
+            packed
+        })
+        .collect()
+}
+
+/// Multiplicative hash of a packed syncmer, a bijection
+#[inline]
+fn spill_hash(packed: u64) -> u64 {
+    packed.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+/// Bucket of a packed syncmer: its hash mapped onto 0..buckets
+#[inline]
+fn bucket(packed: u64, buckets: usize) -> usize {
+    ((spill_hash(packed) as u128 * buckets as u128) >> 64) as usize
+}
+
+/// Distinguishes the spill directories of counters in one process
+static DISK_COUNTERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Syncmer counter with a memory budget, spilling syncmers to on-disk buckets
+///
+/// Each batch of sequence is extracted and appended to bucket files by hash, and
+/// [`DiskCounter::for_each_bucket`] sorts and counts the buckets one at a time. Counting a
+/// bucket takes up to 24 bytes per syncmer occurrence in it, so the number of buckets needed
+/// is about the total occurrences * 24 / budget. That total is only known once everything is
+/// spilled: buckets over the budget are then split by hash into up to [`DEFAULT_BUCKETS`]
+/// parts, recursively, until each part fits or holds copies of a single syncmer (counted
+/// without being read). Memory is about the budget while adding and while counting. The
+/// results are identical to [`SyncmerCounter`]'s.
+pub struct DiskCounter {
+    params: Params,
+    dir: PathBuf,
+    memory: usize,
+    batch_bases: usize,
+    buckets: Vec<BufWriter<File>>,
+}
+
+impl DiskCounter {
+    /// Spill into a new directory under tmp_dir with [`DEFAULT_BUCKETS`] buckets, reading about
+    /// memory / 4 bases of sequence per batch (leaving room for their syncmers) and counting
+    /// about memory bytes of syncmers at a time
+    pub fn new<P: AsRef<Path>>(params: Params, tmp_dir: P, memory: usize) -> Result<Self, Error> {
+        DiskCounter::with_buckets(params, tmp_dir, memory, DEFAULT_BUCKETS)
+    }
+
+    pub fn with_buckets<P: AsRef<Path>>(
+        params: Params,
+        tmp_dir: P,
+        memory: usize,
+        buckets: usize,
+    ) -> Result<Self, Error> {
+        check_count_params(&params)?;
+        if buckets == 0 {
+            return Err(Error::InvalidParams(
+                "At least one bucket is needed".to_string(),
+            ));
+        }
+        let dir = tmp_dir.as_ref().join(format!(
+            "syncmers-count-{}-{}",
+            std::process::id(),
+            DISK_COUNTERS.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+        let buckets = (0..buckets)
+            .map(|i| Ok(BufWriter::new(File::create(bucket_path(&dir, i))?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(DiskCounter {
+            params: params.with_canonical(true),
+            dir,
+            memory,
+            batch_bases: (memory / 4).max(1),
+            buckets,
+        })
+    }
+
+    /// Spill the syncmers of a batch of records
+    pub fn add_records(&mut self, records: &[Record]) -> Result<(), Error> {
+        let n = self.buckets.len();
+        for packed in extract(&self.params, records) {
+            self.buckets[bucket(packed, n)].write_all(&packed.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Spill every record of a FASTA/FASTQ file, in batches within the memory budget
+    pub fn add_fasta<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let batch_bases = self.batch_bases;
+        for_each_batch(path, batch_bases, |batch| self.add_records(batch))
+    }
+
+    /// Sort and count each bucket in turn, calling f with its counts (sorted by syncmer).
+    /// Buckets over the memory budget are split first
+    pub fn for_each_bucket<F>(mut self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(Vec<(u64, u32)>) -> Result<(), Error>,
+    {
+        let n = self.buckets.len();
+        for writer in self.buckets.drain(..) {
+            writer.into_inner().map_err(|e| e.into_error())?;
+        }
+        let limit = (self.memory / BYTES_PER_OCCURRENCE).max(1) as u64;
+        for i in 0..n {
+            // Hashes of bucket i: those with i <= hash * n / 2^64 < i + 1
+            let range = |i: usize| ((i as u128) << 64).div_ceil(n as u128);
+            count_part(
+                &bucket_path(&self.dir, i),
+                range(i),
+                range(i + 1),
+                limit,
+                &mut f,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Counts of all buckets, in memory (16 bytes per distinct syncmer)
+    pub fn finish(self) -> Result<Counts, Error> {
+        let params = self.params.clone();
+        let mut counts = Vec::new();
+        self.for_each_bucket(|bucket| {
+            counts.extend(bucket);
+            Ok(())
+        })?;
+        counts.par_sort_unstable_by_key(|x| x.0);
+        Ok(Counts::from_sorted(params, counts))
+    }
+
+    /// Histogram as [`Counts::histogram`], holding only one bucket in memory at a time
+    pub fn finish_histogram(self, max_count: usize) -> Result<Vec<u64>, Error> {
+        let mut histogram = vec![0; max_count + 1];
+        self.for_each_bucket(|bucket| {
+            add_to_histogram(&mut histogram, &bucket);
+            Ok(())
+        })?;
+        Ok(histogram)
+    }
+
+    /// Dump as [`Counts::write_dump`] but in bucket order, holding only one bucket in memory
+    /// at a time. Returns the histogram too, as [`DiskCounter::finish_histogram`]
+    pub fn finish_dump<W: Write>(
+        self,
+        min_count: u32,
+        max_count: usize,
+        mut out: W,
+    ) -> Result<Vec<u64>, Error> {
+        let k = self.params.k();
+        let mut histogram = vec![0; max_count + 1];
+        self.for_each_bucket(|bucket| {
+            add_to_histogram(&mut histogram, &bucket);
+            write_dump_lines(&bucket, k, min_count, &mut out)
+        })?;
+        out.flush()?;
+        Ok(histogram)
+    }
+}
+
+/// Count the spilled syncmers at path, whose hashes are in lo..hi, and delete the file. Over
+/// limit occurrences, they are split by hash into parts of that range first
+fn count_part<F>(path: &Path, lo: u128, hi: u128, limit: u64, f: &mut F) -> Result<(), Error>
+where
+    F: FnMut(Vec<(u64, u32)>) -> Result<(), Error>,
+{
+    let occurrences = std::fs::metadata(path)?.len() / 8;
+    if occurrences <= limit {
+        return f(sort_and_count(read_bucket(path)?));
+    }
+
+    // Parts of about half the budget, to leave room for uneven hashing. Part j holds the
+    // hashes with j <= (hash - lo) * parts / (hi - lo) < j + 1
+    let parts = (2 * occurrences.div_ceil(limit)).min(DEFAULT_BUCKETS as u64) as u128;
+    let width = hi - lo;
+    let paths = (0..parts)
+        .map(|j| path.with_extension(format!("{}.bin", j)))
+        .collect::<Vec<_>>();
+    let mut writers = paths
+        .iter()
+        .map(|x| Ok(BufWriter::new(File::create(x)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    // First syncmer of each part, and whether any other syncmer follows it there
+    let mut first = vec![None; paths.len()];
+    let mut mixed = vec![false; paths.len()];
+    for_each_packed(path, |packed| {
+        let j = ((spill_hash(packed) as u128 - lo) * parts / width) as usize;
+        match first[j] {
+            None => first[j] = Some(packed),
+            Some(x) => mixed[j] |= x != packed,
+        }
+        writers[j].write_all(&packed.to_le_bytes())
+    })?;
+    for writer in writers {
+        writer.into_inner().map_err(|e| e.into_error())?;
+    }
+    std::fs::remove_file(path)?;
+
+    for (j, path) in paths.iter().enumerate() {
+        match first[j] {
+            Some(packed) if !mixed[j] => {
+                let count = std::fs::metadata(path)?.len() / 8;
+                std::fs::remove_file(path)?;
+                f(vec![(packed, count.min(u32::MAX as u64) as u32)])?;
+            }
+            Some(_) => {
+                let part = |j: u128| lo + (j * width).div_ceil(parts);
+                count_part(path, part(j as u128), part(j as u128 + 1), limit, f)?;
+            }
+            None => std::fs::remove_file(path)?,
+        }
+    }
+    Ok(())
+}
+
+/// Read a bucket file into memory and delete it
+fn read_bucket(path: &Path) -> Result<Vec<u64>, Error> {
+    let mut packed = Vec::with_capacity((std::fs::metadata(path)?.len() / 8) as usize);
+    for_each_packed(path, |x| {
+        packed.push(x);
+        Ok(())
+    })?;
+    // The bucket is in memory, free its disk space
+    std::fs::remove_file(path)?;
+    Ok(packed)
+}
+
+fn for_each_packed<F>(path: &Path, mut f: F) -> Result<(), Error>
+where
+    F: FnMut(u64) -> std::io::Result<()>,
+{
+    let mut input = BufReader::new(File::open(path)?);
+    let mut buf = [0; 8];
+    loop {
+        match input.read_exact(&mut buf) {
+            Ok(()) => f(u64::from_le_bytes(buf))?,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn add_to_histogram(histogram: &mut [u64], counts: &[(u64, u32)]) {
+    let max_count = histogram.len() - 1;
+    for &(_, count) in counts {
+        histogram[(count as usize).min(max_count)] += 1;
+    }
+}
+
+/// "kmer count" lines for the counts of at least min_count
+fn write_dump_lines<W: Write>(
+    counts: &[(u64, u32)],
+    k: usize,
+    min_count: u32,
+    out: &mut W,
+) -> Result<(), Error> {
+    for &(packed, count) in counts.iter().filter(|x| x.1 >= min_count) {
+        out.write_all(&unpack(packed, k))?;
+        writeln!(out, " {}", count)?;
+    }
+    Ok(())
+}
+
+impl Drop for DiskCounter {
+    fn drop(&mut self) {
+        self.buckets.clear();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn bucket_path(dir: &Path, i: usize) -> PathBuf {
+    dir.join(format!("bucket-{:05}.bin", i))
+}
+
+/// Write a histogram in jellyfish `histo` format: "count frequency" lines for non-empty bins
+pub fn write_histogram<W: Write>(histogram: &[u64], mut out: W) -> Result<(), Error> {
+    for (count, &frequency) in histogram.iter().enumerate() {
+        if frequency > 0 {
+            writeln!(out, "{} {}", count, frequency)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Counts of canonical syncmers
+///
+/// ```rust
+/// # use syncmers::count::SyncmerCounter;
+/// # use syncmers::fasta::Record;
+/// # use syncmers::Params;
+/// let mut counter = SyncmerCounter::new(Params::new(5, 2, &[2]).unwrap()).unwrap();
+/// let record = Record { id: "r".to_string(), seq: b"CCAGTGTTTACGGCCAGTGTTTACGG".to_vec() };
+/// counter.add_records(&[record]);
+/// let counts = counter.finish();
+/// assert_eq!(counts.get(b"GTAAA"), 2);
+/// assert_eq!(counts.get(b"TTTAC"), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counts {
+    params: Params,
+    counts: Vec<(u64, u32)>,
+}
+
+impl Counts {
+    /// Counts from (packed canonical syncmer, count), sorted by syncmer
+    pub fn from_sorted(params: Params, counts: Vec<(u64, u32)>) -> Self {
+        debug_assert!(counts.windows(2).all(|x| x[0].0 < x[1].0));
+        Counts { params, counts }
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// Number of distinct syncmers
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Total number of syncmer occurrences
+    pub fn total(&self) -> u64 {
+        self.counts.iter().map(|x| x.1 as u64).sum()
+    }
+
+    /// Count of a kmer (either strand), 0 if it isn't a counted syncmer
+    pub fn get(&self, kmer: &[u8]) -> u32 {
+        let k = self.params.k();
+        let Some(packed) = (kmer.len() == k).then(|| pack(kmer)).flatten() else {
+            return 0;
+        };
+        let canonical = packed.min(revcomp_packed(packed, k));
+        match self.counts.binary_search_by_key(&canonical, |x| x.0) {
+            Ok(i) => self.counts[i].1,
+            Err(_) => 0,
+        }
+    }
+
+    /// (packed canonical syncmer, count), sorted by syncmer
+    pub fn counts(&self) -> &[(u64, u32)] {
+        &self.counts
+    }
+
+    /// Number of distinct syncmers seen each number of times: histogram[c] for c in
+    /// 0..=max_count, with higher counts added to the last bin (as jellyfish's `--high`)
+    pub fn histogram(&self, max_count: usize) -> Vec<u64> {
+        let mut histogram = vec![0; max_count + 1];
+        add_to_histogram(&mut histogram, &self.counts);
+        histogram
+    }
+
+    /// jellyfish `histo` format, see [`write_histogram`]
+    pub fn write_histogram<W: Write>(&self, max_count: usize, out: W) -> Result<(), Error> {
+        write_histogram(&self.histogram(max_count), out)
+    }
+
+    /// jellyfish `dump -c` format: "kmer count" lines for syncmers counted at least
+    /// min_count times
+    pub fn write_dump<W: Write>(&self, min_count: u32, mut out: W) -> Result<(), Error> {
+        write_dump_lines(&self.counts, self.params.k(), min_count, &mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::revcomp;
+    use crate::test_util::random_seq;
+    use std::collections::HashMap;
+
+    #[test]
+    pub fn test_pack() {
+        let kmer = random_seq(31, 3);
+        let packed = pack(&kmer).unwrap();
+        assert_eq!(unpack(packed, 31), kmer);
+        let mut rc = kmer.clone();
+        revcomp(&mut rc);
+        assert_eq!(revcomp_packed(packed, 31), pack(&rc).unwrap());
+        assert_eq!(pack(b"ACNGT"), None);
+        assert!(pack(b"AAAC") < pack(b"AACA"));
+    }
+
+    #[test]
+    pub fn test_counts_match_naive() {
+        let params = Params::new(21, 11, &[5]).unwrap();
+        let genome = random_seq(20_000, 5);
+        // Reads from both strands, so every syncmer is seen several times
+        let records = (0..40)
+            .map(|i| {
+                let start = i * 450;
+                let mut seq = genome[start..start + 2_000].to_vec();
+                if i % 2 == 1 {
+                    revcomp(&mut seq);
+                }
+                Record {
+                    id: i.to_string(),
+                    seq,
+                }
+            })
+            .chain(std::iter::once(Record {
+                // A tandem repeat, whose syncmers each occur hundreds of times
+                id: "repeat".to_string(),
+                seq: genome[..37].repeat(300),
+            }))
+            .collect::<Vec<_>>();
+
+        let mut counter = SyncmerCounter::new(params.clone()).unwrap();
+        counter.add_records(&records[..15]);
+        counter.add_records(&records[15..]);
+        let counts = counter.finish();
+
+        let canonical = params.clone().with_canonical(true);
+        let mut naive: HashMap<Vec<u8>, u32> = HashMap::new();
+        for record in &records {
+            for syncmer in canonical.syncmers(&record.seq) {
+                let mut kmer = record.seq[syncmer.pos..syncmer.pos + 21].to_vec();
+                if syncmer.strand == Strand::Reverse {
+                    revcomp(&mut kmer);
+                }
+                *naive.entry(kmer).or_default() += 1;
+            }
+        }
+        assert_eq!(counts.len(), naive.len());
+        for (kmer, &count) in &naive {
+            assert_eq!(counts.get(kmer), count);
+        }
+
+        let histogram = counts.histogram(10);
+        assert_eq!(histogram.iter().sum::<u64>(), counts.len() as u64);
+        let mut out = Vec::new();
+        counts.write_dump(3, &mut out).unwrap();
+        let dumped = String::from_utf8(out).unwrap();
+        assert_eq!(
+            dumped.lines().count(),
+            naive.values().filter(|&&x| x >= 3).count()
+        );
+        assert!(dumped
+            .lines()
+            .all(|x| x.len() > 22 && x.as_bytes()[21] == b' '));
+
+        // Spilling to a few small buckets gives the same counts
+        let disk_with = |memory, buckets| {
+            let mut counter =
+                DiskCounter::with_buckets(params.clone(), std::env::temp_dir(), memory, buckets)
+                    .unwrap();
+            for batch in records.chunks(7) {
+                counter.add_records(batch).unwrap();
+            }
+            counter
+        };
+        let disk = |buckets| disk_with(4_000, buckets);
+        // Buckets of hundreds of occurrences, over the 166 the budget allows: split first
+        assert_eq!(disk(8).finish().unwrap(), counts);
+        // Thousands of occurrences against a budget of 2, more than one split can handle
+        assert_eq!(disk_with(48, 1).finish().unwrap(), counts);
+        assert_eq!(disk(3).finish_histogram(10).unwrap(), histogram);
+        let mut streamed = Vec::new();
+        assert_eq!(
+            disk(5).finish_dump(3, 10, &mut streamed).unwrap(),
+            histogram
+        );
+        let mut streamed = String::from_utf8(streamed)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        streamed.sort();
+        assert_eq!(
+            streamed,
+            dumped.lines().map(String::from).collect::<Vec<_>>()
+        );
+        let dir = disk(1).dir.clone();
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,326 @@
+//! All-vs-all distances between syncmer sketches, written as PHYLIP or TSV matrices
+//!
+//! Rows are computed in parallel, a block at a time, and written as soon as they are done, so
+//! only the sketches and one block of rows are held in memory.
+
+use std::io::{BufRead, Write};
+
+use rayon::prelude::*;
+
+use crate::sketch::{mash_distance, BottomKSketch, Sketch};
+use crate::Error;
+
+/// Number of rows computed (in parallel) before being written
+pub const DEFAULT_BLOCK_SIZE: usize = 256;
+
+/// Sketches that a pairwise distance (0 identical, 1 unrelated) can be computed between
+pub trait SketchDistance {
+    fn distance(&self, other: &Self) -> Result<f64, Error>;
+}
+
+impl SketchDistance for Sketch {
+    /// Mash distance from the FracMinHash Jaccard index
+    fn distance(&self, other: &Self) -> Result<f64, Error> {
+        Ok(mash_distance(self.jaccard(other)?, self.params().k()))
+    }
+}
+
+impl SketchDistance for BottomKSketch {
+    fn distance(&self, other: &Self) -> Result<f64, Error> {
+        BottomKSketch::distance(self, other)
+    }
+}
+
+/// Output matrix format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatrixFormat {
+    /// Square PHYLIP: the number of taxa, then one row per taxon
+    Phylip,
+    /// Tab separated, with a header row of labels
+    Tsv,
+}
+
+/// Square, symmetric distance matrix with labels
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistanceMatrix {
+    labels: Vec<String>,
+    distances: Vec<f64>,
+}
+
+impl DistanceMatrix {
+    pub fn new(labels: Vec<String>, distances: Vec<f64>) -> Self {
+        assert_eq!(labels.len() * labels.len(), distances.len());
+        DistanceMatrix { labels, distances }
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        self.distances[i * self.len() + j]
+    }
+
+    pub fn row(&self, i: usize) -> &[f64] {
+        &self.distances[i * self.len()..(i + 1) * self.len()]
+    }
+
+    /// Read a square PHYLIP or TSV matrix, as written by [`DistanceMatrix::write`]. PHYLIP
+    /// rows end with n whitespace separated distances, and the rest of the row is the label
+    pub fn read<R: BufRead>(input: R, format: MatrixFormat) -> Result<Self, Error> {
+        let mut lines = input
+            .lines()
+            .filter(|x| !matches!(x, Ok(line) if line.trim().is_empty()));
+        let header = match lines.next() {
+            Some(line) => line?,
+            None => return Err(Error::Format("Empty distance matrix".to_string())),
+        };
+
+        let n = match format {
+            MatrixFormat::Phylip => header
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| Error::Format(format!("Invalid PHYLIP taxa count {:?}", header)))?,
+            MatrixFormat::Tsv => header.split('\t').count() - 1,
+        };
+
+        let mut labels = Vec::with_capacity(n);
+        let mut distances = Vec::with_capacity(n * n);
+        for line in lines {
+            let line = line?;
+            let (label, fields) = match format {
+                MatrixFormat::Phylip => {
+                    let fields = line.split_whitespace().rev().take(n).collect::<Vec<_>>();
+                    let mut label = line.trim_end();
+                    for field in &fields {
+                        label = label[..label.len() - field.len()].trim_end();
+                    }
+                    (label.trim_start(), fields.into_iter().rev().collect())
+                }
+                MatrixFormat::Tsv => {
+                    let mut fields = line.split('\t');
+                    (fields.next().unwrap_or(""), fields.collect::<Vec<_>>())
+                }
+            };
+            labels.push(label.to_string());
+            for field in fields {
+                distances.push(field.trim().parse::<f64>().map_err(|_| {
+                    Error::Format(format!("Invalid distance {:?} in matrix", field))
+                })?);
+            }
+        }
+
+        if labels.len() != n || distances.len() != n * n {
+            return Err(Error::Format(format!(
+                "Expected a square matrix of {} rows",
+                n
+            )));
+        }
+        Ok(DistanceMatrix::new(labels, distances))
+    }
+
+    pub fn write<W: Write>(&self, format: MatrixFormat, mut out: W) -> Result<(), Error> {
+        write_header(&self.labels, format, &mut out)?;
+        for i in 0..self.len() {
+            write_row(&self.labels[i], self.row(i), format, &mut out)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compute the full distance matrix in memory (n^2 floats). Each pair is computed once
+pub fn distance_matrix<T: SketchDistance + Sync>(
+    labels: Vec<String>,
+    sketches: &[T],
+) -> Result<DistanceMatrix, Error> {
+    assert_eq!(labels.len(), sketches.len());
+    let n = sketches.len();
+    let upper = (0..n)
+        .into_par_iter()
+        .map(|i| distances(&sketches[i], &sketches[i + 1..]))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut matrix = vec![0.0; n * n];
+    for (i, row) in upper.iter().enumerate() {
+        for (j, &d) in (i + 1..n).zip(row) {
+            matrix[i * n + j] = d;
+            matrix[j * n + i] = d;
+        }
+    }
+    Ok(DistanceMatrix::new(labels, matrix))
+}
+
+/// Compute and write all-vs-all distances without holding the whole matrix,
+/// block_size rows at a time. Pairs within a block are computed once, pairs with earlier rows
+/// are computed again rather than kept
+pub fn write_distances<T: SketchDistance + Sync, W: Write>(
+    labels: &[String],
+    sketches: &[T],
+    format: MatrixFormat,
+    block_size: usize,
+    mut out: W,
+) -> Result<(), Error> {
+    assert_eq!(labels.len(), sketches.len());
+    assert!(block_size > 0);
+
+    write_header(labels, format, &mut out)?;
+    for start in (0..sketches.len()).step_by(block_size) {
+        let end = (start + block_size).min(sketches.len());
+        // Columns before the block, then the upper triangle from the diagonal on
+        let mut rows = (start..end)
+            .into_par_iter()
+            .map(|i| {
+                let mut row = distances(&sketches[i], &sketches[..start])?;
+                row.resize(i, 0.0);
+                row.push(0.0);
+                row.extend(distances(&sketches[i], &sketches[i + 1..])?);
+                Ok(row)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        for i in start..end {
+            for j in start..i {
+                rows[i - start][j] = rows[j - start][i];
+            }
+        }
+        for (i, row) in (start..end).zip(rows) {
+            write_row(&labels[i], &row, format, &mut out)?;
+        }
+    }
+    Ok(())
+}
+
+fn distances<T: SketchDistance>(sketch: &T, others: &[T]) -> Result<Vec<f64>, Error> {
+    others.iter().map(|other| sketch.distance(other)).collect()
+}
+
+fn write_header<W: Write>(
+    labels: &[String],
+    format: MatrixFormat,
+    out: &mut W,
+) -> Result<(), Error> {
+    match format {
+        MatrixFormat::Phylip => writeln!(out, "{}", labels.len())?,
+        MatrixFormat::Tsv => writeln!(out, "\t{}", labels.join("\t"))?,
+    }
+    Ok(())
+}
+
+fn write_row<W: Write>(
+    label: &str,
+    row: &[f64],
+    format: MatrixFormat,
+    out: &mut W,
+) -> Result<(), Error> {
+    let separator = match format {
+        MatrixFormat::Phylip => " ",
+        MatrixFormat::Tsv => "\t",
+    };
+    write!(out, "{}", label)?;
+    for d in row {
+        write!(out, "{}{:.6}", separator, d)?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Params;
+
+    #[test]
+    pub fn test_streamed_matches_in_memory() {
+        let params = Params::new(15, 5, &[0]).unwrap();
+        let seqs: [&[u8]; 3] = [
+            b"ACGATCGATCGGGATCTAGCTAGCTAGGCTAGCATCGACTTTAGCTAGCATCGAGGACTAGGACTTATC",
+            b"ACGATCGATCGGGATCTAGCTAGCTAGGCTAGCATCGACTTTAGCTAGCATCGAGGACTAGGACTTATG",
+            b"TTTTGCGCGATATCGGCGCTAGCATCATATACGCGATATATCGCGCGCTATTTTATCAGCGACGTACGA",
+        ];
+        let sketches = seqs
+            .iter()
+            .map(|x| Sketch::from_sequence(params.clone(), 1, x))
+            .collect::<Vec<_>>();
+        let labels = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+
+        let matrix = distance_matrix(labels.clone(), &sketches).unwrap();
+        assert_eq!(matrix.get(0, 0), 0.0);
+        assert_eq!(matrix.get(0, 1), matrix.get(1, 0));
+        assert!(matrix.get(0, 1) < matrix.get(0, 2));
+
+        let mut in_memory = Vec::new();
+        matrix.write(MatrixFormat::Phylip, &mut in_memory).unwrap();
+        let mut streamed = Vec::new();
+        write_distances(&labels, &sketches, MatrixFormat::Phylip, 2, &mut streamed).unwrap();
+        assert_eq!(in_memory, streamed);
+
+        let mut tsv = Vec::new();
+        matrix.write(MatrixFormat::Tsv, &mut tsv).unwrap();
+        let read = DistanceMatrix::read(&tsv[..], MatrixFormat::Tsv).unwrap();
+        assert_eq!(read.labels(), matrix.labels());
+        assert!((read.get(0, 2) - matrix.get(0, 2)).abs() < 1e-6);
+        assert_eq!(
+            DistanceMatrix::read(&in_memory[..], MatrixFormat::Phylip).unwrap(),
+            read
+        );
+        assert!(String::from_utf8(streamed)
+            .unwrap()
+            .starts_with("3\na 0.000000 "));
+    }
+
+    #[test]
+    pub fn test_read_labels() {
+        // Numeric TSV labels and PHYLIP labels with whitespace
+        let matrix = DistanceMatrix::new(
+            vec!["7".to_string(), "E. coli K-12".to_string()],
+            vec![0.0, 0.25, 0.25, 0.0],
+        );
+        for format in [MatrixFormat::Phylip, MatrixFormat::Tsv] {
+            let mut out = Vec::new();
+            matrix.write(format, &mut out).unwrap();
+            assert_eq!(DistanceMatrix::read(&out[..], format).unwrap(), matrix);
+        }
+        let single = DistanceMatrix::new(vec!["7".to_string()], vec![0.0]);
+        let mut out = Vec::new();
+        single.write(MatrixFormat::Tsv, &mut out).unwrap();
+        assert_eq!(out, b"\t7\n7\t0.000000\n");
+        assert_eq!(
+            DistanceMatrix::read(&out[..], MatrixFormat::Tsv).unwrap(),
+            single
+        );
+        assert!(DistanceMatrix::read(&out[..], MatrixFormat::Phylip).is_err());
+    }
+
+    #[test]
+    pub fn test_pairs_computed_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        struct Point(f64);
+        impl SketchDistance for Point {
+            fn distance(&self, other: &Self) -> Result<f64, Error> {
+                CALLS.fetch_add(1, Ordering::Relaxed);
+                Ok((self.0 - other.0).abs())
+            }
+        }
+
+        let points = (0..5).map(|x| Point(x as f64)).collect::<Vec<_>>();
+        let labels = (0..5).map(|x| x.to_string()).collect::<Vec<_>>();
+        let matrix = distance_matrix(labels.clone(), &points).unwrap();
+        assert_eq!(CALLS.swap(0, Ordering::Relaxed), 10);
+        assert_eq!(matrix.get(4, 1), 3.0);
+        assert_eq!(matrix.get(1, 4), 3.0);
+
+        let mut in_memory = Vec::new();
+        matrix.write(MatrixFormat::Tsv, &mut in_memory).unwrap();
+        let mut streamed = Vec::new();
+        write_distances(&labels, &points, MatrixFormat::Tsv, 5, &mut streamed).unwrap();
+        assert_eq!(CALLS.load(Ordering::Relaxed), 10);
+        assert_eq!(in_memory, streamed);
+    }
+}
@@ -0,0 +1,39 @@
+use std::fmt;
+
+/// Errors from parameter validation, file I/O and comparisons between syncmer sets
+#[derive(Debug)]
+pub enum Error {
+    /// k, s or ts (or another parameter) failed validation
+    InvalidParams(String),
+    /// Two sketches or files were built with different parameters and can't be compared
+    IncompatibleParams(String),
+    /// Malformed input file
+    Format(String),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidParams(msg) => write!(f, "Invalid parameters: {}", msg),
+            Error::IncompatibleParams(msg) => write!(f, "Incompatible parameters: {}", msg),
+            Error::Format(msg) => write!(f, "Invalid format: {}", msg),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
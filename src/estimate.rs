@@ -0,0 +1,314 @@
+//! Genome size, coverage and heterozygosity from syncmer abundance histograms, as GenomeScope
+//! does for kmers
+//!
+//! The histogram above the error peak is fit with a diploid model: heterozygous syncmers
+//! (one haplotype) form a negative binomial peak at the per-haplotype coverage λ, and
+//! homozygous ones a peak at 2λ. The heterozygosity follows from the ratio of the two peaks
+//! and k. Only a fraction (the density) of kmers are syncmers, so the syncmer count is divided
+//! by the density to give a genome size in bases.
+//!
+//! With a single peak the fit can't tell a homozygous genome at coverage 2λ from a fully
+//! heterozygous one at λ; ties are resolved towards the lower heterozygosity.
+
+use std::io::BufRead;
+
+use crate::{theory, Error, Params};
+
+/// Result of [`fit_histogram`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramFit {
+    /// Per-haplotype syncmer coverage (the heterozygous peak)
+    pub coverage: f64,
+    /// Per-base heterozygosity
+    pub heterozygosity: f64,
+    /// Haploid genome length in syncmers. Syncmers in the last bin of the histogram are
+    /// extrapolated from the fitted peaks, so repeats counted that often or more are left out
+    pub genome_syncmers: f64,
+    /// Haploid genome length in bases (genome_syncmers / density)
+    pub genome_size: f64,
+    /// Syncmers counted fewer times are taken as sequencing errors
+    pub error_cutoff: usize,
+    /// Fraction of kmers that are syncmers
+    pub density: f64,
+    /// Overdispersion of the peaks: variance = mean * (1 + bias)
+    pub bias: f64,
+    /// 1 - residual sum of squares / sum of squares of the fitted histogram bins
+    pub model_fit: f64,
+}
+
+/// Read a jellyfish-style histogram ("count frequency" lines) into histogram[count]
+pub fn read_histogram<R: BufRead>(input: R) -> Result<Vec<u64>, Error> {
+    let mut histogram = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let mut parse = || -> Option<u64> { fields.next()?.parse().ok() };
+        let (Some(count), Some(frequency)) = (parse(), parse()) else {
+            return Err(Error::Format(format!("Invalid histogram line: {}", line)));
+        };
+        let count = count as usize;
+        if histogram.len() <= count {
+            histogram.resize(count + 1, 0);
+        }
+        histogram[count] += frequency;
+    }
+    Ok(histogram)
+}
+
+/// Lanczos approximation of ln Γ(x), x > 0
+fn ln_gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const C: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection
+        std::f64::consts::PI.ln() - (std::f64::consts::PI * x).sin().ln() - ln_gamma(1.0 - x)
+    } else {
+        let x = x - 1.0;
+        let t = x + G + 0.5;
+        let series = C[1..]
+            .iter()
+            .enumerate()
+            .fold(C[0], |sum, (i, &c)| sum + c / (x + i as f64 + 1.0));
+        0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+    }
+}
+
+/// Negative binomial probability of c, with the given mean and variance mean * (1 + bias)
+fn negative_binomial(c: usize, mean: f64, bias: f64) -> f64 {
+    let size = mean / bias;
+    let c = c as f64;
+    (ln_gamma(c + size) - ln_gamma(size) - ln_gamma(c + 1.0)
+        + size * (size / (size + mean)).ln()
+        + c * (mean / (size + mean)).ln())
+    .exp()
+}
+
+/// Non-negative least squares amplitudes of two components x and y fitting h
+fn fit_amplitudes(x: &[f64], y: &[f64], h: &[f64]) -> (f64, f64) {
+    let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>();
+    let (xx, yy, xy, xh, yh) = (dot(x, x), dot(y, y), dot(x, y), dot(x, h), dot(y, h));
+    let det = xx * yy - xy * xy;
+    if det > 0.0 {
+        let a = (xh * yy - yh * xy) / det;
+        let b = (yh * xx - xh * xy) / det;
+        if a >= 0.0 && b >= 0.0 {
+            return (a, b);
+        }
+    }
+    // One of the components is unused: keep the better single fit
+    let a = (xh / xx).max(0.0);
+    let b = (yh / yy).max(0.0);
+    if a * xh > b * yh {
+        (a, 0.0)
+    } else {
+        (0.0, b)
+    }
+}
+
+/// First local minimum of the histogram, where sequencing errors give way to genomic syncmers
+fn error_cutoff(histogram: &[u64]) -> usize {
+    (2..histogram.len().saturating_sub(1))
+        .find(|&c| histogram[c] <= histogram[c + 1])
+        .unwrap_or(1)
+}
+
+/// Fit a syncmer histogram (histogram[c] distinct syncmers seen c times, the last bin holding
+/// all higher counts) with the [`theory::calibrated_density`] of params' canonical syncmers, which is
+/// what [`crate::count`] counts. None if there is no peak to fit
+pub fn fit_histogram(histogram: &[u64], params: &Params) -> Option<HistogramFit> {
+    let density = theory::calibrated_density(&params.clone().with_canonical(true));
+    fit_histogram_with_density(histogram, params.k(), density)
+}
+
+/// [`fit_histogram`] with an explicit density, e.g. measured on a related genome
+pub fn fit_histogram_with_density(
+    histogram: &[u64],
+    k: usize,
+    density: f64,
+) -> Option<HistogramFit> {
+    let cutoff = error_cutoff(histogram);
+    // The last bin holds the tail of repeats, leave it out of the fit
+    let end = histogram.len().saturating_sub(1);
+    if end <= cutoff + 2 {
+        return None;
+    }
+    let counts = (cutoff..end).collect::<Vec<_>>();
+    let observed = counts
+        .iter()
+        .map(|&c| histogram[c] as f64)
+        .collect::<Vec<_>>();
+    let total = observed.iter().map(|x| x * x).sum::<f64>();
+
+    // (residual, coverage, bias, het amplitude, hom amplitude)
+    let mut best: Option<(f64, f64, f64, f64, f64)> = None;
+    let mut coverage = 1.0;
+    while 2.0 * coverage < end as f64 {
+        for bias in [0.01, 0.1, 0.25, 0.5, 1.0, 2.0] {
+            let het = counts
+                .iter()
+                .map(|&c| negative_binomial(c, coverage, bias))
+                .collect::<Vec<_>>();
+            let hom = counts
+                .iter()
+                .map(|&c| negative_binomial(c, 2.0 * coverage, bias))
+                .collect::<Vec<_>>();
+            let (a, b) = fit_amplitudes(&het, &hom, &observed);
+            let residual = (0..counts.len())
+                .map(|i| (observed[i] - a * het[i] - b * hom[i]).powi(2))
+                .sum::<f64>();
+
+            let better = match best {
+                None => true,
+                Some((r, _, _, ba, bb)) => {
+                    // Prefer the lower heterozygosity among near-equal fits
+                    residual < 0.99 * r
+                        || (residual <= 1.01 * r && a / (a + 2.0 * b) < ba / (ba + 2.0 * bb))
+                }
+            };
+            if better {
+                best = Some((residual, coverage, bias, a, b));
+            }
+        }
+        coverage *= 1.01;
+    }
+
+    let (residual, coverage, bias, het, hom) = best?;
+    if het + hom <= 0.0 {
+        return None;
+    }
+    // Distinct het syncmers: 2 (1 - (1 - r)^k) G, hom: (1 - r)^k G
+    let heterozygosity = 1.0 - (2.0 * hom / (het + 2.0 * hom)).powf(1.0 / k as f64);
+    // The last bin only gives a lower bound on its counts: take the occurrences at counts of
+    // end and above from the fitted peaks instead, the mean less the part below end
+    let below = |mean: f64| {
+        (0..end)
+            .map(|c| c as f64 * negative_binomial(c, mean, bias))
+            .sum::<f64>()
+    };
+    let tail = het * (coverage - below(coverage)) + hom * (2.0 * coverage - below(2.0 * coverage));
+    let occurrences = (cutoff..end)
+        .map(|c| c as f64 * histogram[c] as f64)
+        .sum::<f64>()
+        + tail.max(0.0);
+    let genome_syncmers = occurrences / (2.0 * coverage);
+
+    Some(HistogramFit {
+        coverage,
+        heterozygosity,
+        genome_syncmers,
+        genome_size: genome_syncmers / density,
+        error_cutoff: cutoff,
+        density,
+        bias,
+        model_fit: 1.0 - residual / total,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::count::SyncmerCounter;
+    use crate::fasta::Record;
+    use crate::test_util::{random_seq, XorShift};
+
+    #[test]
+    pub fn test_ln_gamma() {
+        assert!((ln_gamma(1.0)).abs() < 1e-10);
+        assert!((ln_gamma(5.0) - 24f64.ln()).abs() < 1e-10);
+        assert!((ln_gamma(0.5) - std::f64::consts::PI.sqrt().ln()).abs() < 1e-10);
+        let total = (0..200)
+            .map(|c| negative_binomial(c, 30.0, 0.5))
+            .sum::<f64>();
+        assert!((total - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    pub fn test_fit_model_histogram() {
+        // 1Mb of syncmers, 1% heterozygosity, coverage 20 per haplotype, plus errors
+        let (k, r, coverage, genome) = (21, 0.01f64, 20.0, 1e6);
+        let hom = (1.0 - r).powi(k as i32) * genome;
+        let het = 2.0 * genome - 2.0 * hom;
+        let mut histogram = (0..200)
+            .map(|c| {
+                let model = het * negative_binomial(c, coverage, 0.2)
+                    + hom * negative_binomial(c, 2.0 * coverage, 0.2);
+                model.round() as u64
+            })
+            .collect::<Vec<_>>();
+        histogram[1] += 5_000_000;
+        histogram[2] += 500_000;
+        histogram[3] += 50_000;
+
+        let fit = fit_histogram_with_density(&histogram, k, 0.1).unwrap();
+        assert!((fit.coverage - coverage).abs() < 0.5);
+        assert!((fit.heterozygosity - r).abs() < 0.001);
+        assert!((fit.genome_syncmers / genome - 1.0).abs() < 0.05);
+        assert!((fit.genome_size / (genome * 10.0) - 1.0).abs() < 0.05);
+        assert!(fit.model_fit > 0.99);
+
+        // A last bin holding all counts from the homozygous peak on, which counted at 40
+        // would lose over 5% of the genome
+        let tail = histogram[40..].iter().sum::<u64>();
+        histogram.truncate(41);
+        histogram[40] = tail;
+        let fit = fit_histogram_with_density(&histogram, k, 0.1).unwrap();
+        assert!((fit.coverage - coverage).abs() < 0.5);
+        assert!((fit.genome_syncmers / genome - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    pub fn test_fit_reads() {
+        // Diploid genome with a SNP every 200 bases on one haplotype, 12x reads per haplotype
+        let length = 40_000;
+        let a = random_seq(length, 31);
+        let mut b = a.clone();
+        for i in (100..length).step_by(200) {
+            b[i] = if b[i] == b'A' { b'C' } else { b'A' };
+        }
+        let mut reads = Vec::new();
+        let mut rng = XorShift::new(7);
+        for haplotype in [&a, &b] {
+            for _ in 0..(12 * length / 1_000) {
+                let start = (rng.next_u64() % (length - 1_000) as u64) as usize;
+                reads.push(Record {
+                    id: reads.len().to_string(),
+                    seq: haplotype[start..start + 1_000].to_vec(),
+                });
+            }
+        }
+
+        let params = Params::new(21, 11, &[5]).unwrap();
+        let mut counter = SyncmerCounter::new(params.clone()).unwrap();
+        counter.add_records(&reads);
+        let histogram = counter.finish().histogram(100);
+        let fit = fit_histogram(&histogram, &params).unwrap();
+        // Canonical kmers favour a smallest smer at the start, below 1 / 11 for t = 5
+        assert_eq!(
+            fit.density,
+            theory::calibrated_density(&params.with_canonical(true))
+        );
+        assert!(fit.density < 1.0 / 11.0 - 0.005);
+        assert!((fit.genome_size / length as f64 - 1.0).abs() < 0.15);
+        assert!((fit.heterozygosity - 0.005).abs() < 0.002);
+    }
+
+    #[test]
+    pub fn test_read_histogram() {
+        let histogram = read_histogram(&b"1 100\n3 20\n\n4 5\n"[..]).unwrap();
+        assert_eq!(histogram, vec![0, 100, 0, 20, 5]);
+        assert!(read_histogram(&b"1\n"[..]).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! Empirical evaluation of a syncmer scheme on a sequence and a mutated copy of it, with the
+//! metrics of Edgar and Dutta et al.: density, conservation, the fraction of bases covered by
+//! (conserved) syncmers and the distances between consecutive syncmers
+//!
+//! The mutated copy must only differ by substitutions, so that positions line up: a syncmer is
+//! conserved when the copy has the same kmer at the same position, which is then a syncmer too.
+//! Counts are kept rather than fractions so that evaluations of several sequences can be merged.
+
+use crate::random::XorShift;
+use crate::{Error, Params};
+
+/// Counts from one or more sequences
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Evaluation {
+    pub bases: usize,
+    pub kmers: usize,
+    pub syncmers: usize,
+    /// Syncmers found unchanged at the same position in the mutated copy
+    pub conserved: usize,
+    /// Bases covered by syncmers
+    pub covered: usize,
+    /// Bases covered by conserved syncmers
+    pub conserved_covered: usize,
+    /// Number of consecutive syncmers at each distance
+    pub gaps: Vec<u64>,
+}
+
+impl Evaluation {
+    /// Fraction of kmers that are syncmers
+    pub fn density(&self) -> f64 {
+        self.syncmers as f64 / self.kmers.max(1) as f64
+    }
+
+    /// Fraction of syncmers conserved in the mutated copy
+    pub fn conservation(&self) -> f64 {
+        self.conserved as f64 / self.syncmers.max(1) as f64
+    }
+
+    /// Fraction of bases covered by syncmers
+    pub fn coverage(&self) -> f64 {
+        self.covered as f64 / self.bases.max(1) as f64
+    }
+
+    /// Fraction of bases covered by conserved syncmers
+    pub fn conserved_coverage(&self) -> f64 {
+        self.conserved_covered as f64 / self.bases.max(1) as f64
+    }
+
+    /// Longest distance between consecutive syncmers, the window guarantee if there is one
+    pub fn largest_gap(&self) -> usize {
+        self.gaps.iter().rposition(|&x| x > 0).unwrap_or(0)
+    }
+
+    pub fn mean_gap(&self) -> f64 {
+        let n = self.gaps.iter().sum::<u64>();
+        let total = self
+            .gaps
+            .iter()
+            .enumerate()
+            .map(|(d, &x)| d as u64 * x)
+            .sum::<u64>();
+        total as f64 / n.max(1) as f64
+    }
+
+    /// Add the counts of another evaluation
+    pub fn merge(&mut self, other: &Evaluation) {
+        self.bases += other.bases;
+        self.kmers += other.kmers;
+        self.syncmers += other.syncmers;
+        self.conserved += other.conserved;
+        self.covered += other.covered;
+        self.conserved_covered += other.conserved_covered;
+        if self.gaps.len() < other.gaps.len() {
+            self.gaps.resize(other.gaps.len(), 0);
+        }
+        for (x, y) in self.gaps.iter_mut().zip(&other.gaps) {
+            *x += y;
+        }
+    }
+}
+
+/// Bases covered by kmers starting at sorted positions
+fn covered_bases(positions: impl Iterator<Item = usize>, k: usize) -> usize {
+    let (mut covered, mut end) = (0, 0);
+    for pos in positions {
+        covered += pos + k - pos.max(end);
+        end = pos + k;
+    }
+    covered
+}
+
+/// Copy of seq with each ACGT base substituted with probability rate, by a different base
+pub fn mutate(seq: &[u8], rate: f64, seed: u64) -> Vec<u8> {
+    let mut rng = XorShift::new(seed);
+    seq.iter()
+        .map(|&base| {
+            let Some(i) = b"ACGT".iter().position(|&x| x == base.to_ascii_uppercase()) else {
+                return base;
+            };
+            if rng.next_f64() < rate {
+                b"ACGT"[(i + 1 + (rng.next_u64() % 3) as usize) % 4]
+            } else {
+                base
+            }
+        })
+        .collect()
+}
+
+/// Evaluate params on seq and a copy of it with substitutions only
+pub fn evaluate(params: &Params, seq: &[u8], mutated: &[u8]) -> Result<Evaluation, Error> {
+    if seq.len() != mutated.len() {
+        return Err(Error::InvalidParams(format!(
+            "The mutated sequence must have substitutions only, got lengths {} and {}",
+            seq.len(),
+            mutated.len()
+        )));
+    }
+    let k = params.k();
+    let original = params.syncmers(seq);
+    let copy = params.syncmers(mutated);
+
+    // Both lists are sorted by position
+    let mut conserved = Vec::new();
+    let mut j = 0;
+    for x in &original {
+        while j < copy.len() && copy[j].pos < x.pos {
+            j += 1;
+        }
+        if j < copy.len()
+            && copy[j].pos == x.pos
+            && seq[x.pos..x.pos + k] == mutated[x.pos..x.pos + k]
+        {
+            conserved.push(x.pos);
+        }
+    }
+
+    let mut gaps = Vec::new();
+    for pair in original.windows(2) {
+        let gap = pair[1].pos - pair[0].pos;
+        if gaps.len() <= gap {
+            gaps.resize(gap + 1, 0);
+        }
+        gaps[gap] += 1;
+    }
+
+    Ok(Evaluation {
+        bases: seq.len(),
+        kmers: (seq.len() + 1).saturating_sub(k),
+        syncmers: original.len(),
+        conserved: conserved.len(),
+        covered: covered_bases(original.iter().map(|x| x.pos), k),
+        conserved_covered: covered_bases(conserved.into_iter(), k),
+        gaps,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::random_seq;
+    use crate::theory;
+
+    #[test]
+    pub fn test_evaluate() {
+        let params = Params::new(15, 7, &[4]).unwrap();
+        let seq = random_seq(300_000, 101);
+        let mutated = mutate(&seq, 0.05, 103);
+        let changed = seq.iter().zip(&mutated).filter(|(a, b)| a != b).count();
+        assert!((changed as f64 / seq.len() as f64 - 0.05).abs() < 0.002);
+
+        let x = evaluate(&params, &seq, &mutated).unwrap();
+        assert!((x.density() / theory::density(&params) - 1.0).abs() < 0.05);
+        assert!((x.conservation() - theory::conservation(&params, 0.05)).abs() < 0.02);
+        let expected = theory::conserved_coverage(&params, 0.05);
+        assert!((x.conserved_coverage() - expected).abs() < 0.03);
+        assert!(x.conserved_coverage() < x.coverage());
+        assert_eq!(x.gaps.iter().sum::<u64>() as usize, x.syncmers - 1);
+        assert!((x.mean_gap() * x.density() - 1.0).abs() < 0.01);
+
+        // Unmutated, everything is conserved
+        let same = evaluate(&params, &seq, &seq).unwrap();
+        assert_eq!(same.conserved, same.syncmers);
+        assert_eq!(same.conserved_covered, same.covered);
+
+        let mut merged = x.clone();
+        merged.merge(&same);
+        assert_eq!(merged.syncmers, 2 * x.syncmers);
+        assert_eq!(
+            merged.largest_gap(),
+            x.largest_gap().max(same.largest_gap())
+        );
+
+        assert!(evaluate(&params, &seq, &seq[1..]).is_err());
+    }
+
+    #[test]
+    pub fn test_window_guarantee() {
+        // Closed syncmers are at most k - s apart, open ones have no such bound
+        let seq = random_seq(200_000, 107);
+        let closed = evaluate(&Params::closed(12, 4).unwrap(), &seq, &seq).unwrap();
+        assert_eq!(closed.largest_gap(), 8);
+        let open = evaluate(&Params::open(12, 4).unwrap(), &seq, &seq).unwrap();
+        assert!(open.largest_gap() > 20);
+        assert!(closed.coverage() > open.coverage());
+
+        assert_eq!(covered_bases([0, 2, 10].into_iter(), 4), 10);
+    }
+}
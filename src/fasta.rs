@@ -0,0 +1,137 @@
+//! Minimal FASTA / FASTQ reader, enough to feed files into sketches and indices.
+//! Sequences are uppercased, as the syncmer functions expect.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::Error;
+
+/// A single sequence record. id is the header up to the first whitespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub id: String,
+    pub seq: Vec<u8>,
+}
+
+/// Iterator over the records of a FASTA or FASTQ file (detected per record by '>' or '@')
+pub struct FastaReader<R: BufRead> {
+    reader: R,
+    line: Vec<u8>,
+    // Header line already read while scanning the previous FASTA record
+    pending: Option<Vec<u8>>,
+}
+
+impl FastaReader<BufReader<File>> {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(FastaReader::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> FastaReader<R> {
+    pub fn new(reader: R) -> Self {
+        FastaReader {
+            reader,
+            line: Vec::new(),
+            pending: None,
+        }
+    }
+
+    fn read_line(&mut self) -> Result<bool, Error> {
+        self.line.clear();
+        let n = self.reader.read_until(b'\n', &mut self.line)?;
+        while matches!(self.line.last(), Some(b'\n') | Some(b'\r')) {
+            self.line.pop();
+        }
+        Ok(n > 0)
+    }
+
+    fn next_record(&mut self) -> Result<Option<Record>, Error> {
+        let header = match self.pending.take() {
+            Some(header) => header,
+            None => loop {
+                if !self.read_line()? {
+                    return Ok(None);
+                }
+                if !self.line.is_empty() {
+                    break std::mem::take(&mut self.line);
+                }
+            },
+        };
+
+        let id = String::from_utf8_lossy(&header[1..])
+            .split_whitespace()
+            .next()
+            .unwrap_or("")
+            .to_string();
+
+        let mut seq = Vec::new();
+        match header[0] {
+            b'>' => {
+                while self.read_line()? {
+                    if self.line.first() == Some(&b'>') {
+                        self.pending = Some(std::mem::take(&mut self.line));
+                        break;
+                    }
+                    seq.extend_from_slice(&self.line);
+                }
+            }
+            b'@' => {
+                if !self.read_line()? {
+                    return Err(Error::Format(format!(
+                        "FASTQ record {} has no sequence",
+                        id
+                    )));
+                }
+                seq.extend_from_slice(&self.line);
+                // '+' line, then quality
+                if !self.read_line()? || !self.read_line()? {
+                    return Err(Error::Format(format!("FASTQ record {} is truncated", id)));
+                }
+            }
+            _ => {
+                return Err(Error::Format(format!(
+                    "Expected '>' or '@' at the start of a record, found {:?}",
+                    header[0] as char
+                )))
+            }
+        }
+
+        seq.make_ascii_uppercase();
+        Ok(Some(Record { id, seq }))
+    }
+}
+
+impl<R: BufRead> Iterator for FastaReader<R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_fasta_and_fastq() {
+        let data = b">seq1 description\nACGT\nacgt\n\n>seq2\nTTTT\n";
+        let records = FastaReader::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id, "seq1");
+        assert_eq!(records[0].seq, b"ACGTACGT");
+        assert_eq!(records[1].seq, b"TTTT");
+
+        let data = b"@read1 x\nGGCC\n+\nIIII\n@read2\nAT\n+\nII\n";
+        let records = FastaReader::new(&data[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id, "read1");
+        assert_eq!(records[0].seq, b"GGCC");
+        assert_eq!(records[1].seq, b"AT");
+    }
+}
@@ -0,0 +1,427 @@
+//! Compact, versioned binary format for syncmer sets (positions) and sketches (hashes)
+//!
+//! Layout:
+//!
+//! ```text
+//! magic       8 bytes  "SYNCMRS\0"
+//! version     u16 LE
+//! kind        u8       0 = positions, 1 = hashes
+//! k, s        varint
+//! ts          varint count, then varint each
+//! ordering    u8       0 = lexicographic smer order
+//! canonical   u8
+//! seed        u64 LE
+//! scaled      varint   1 when not downsampled
+//! records     until end of file:
+//!     name    varint length + UTF-8
+//!     values  varint count, then the sorted values delta encoded as varints
+//! ```
+//!
+//! Every file carries the parameters it was built with. Readers check them, and
+//! [`Header::check_compatible`] refuses to mix files built with different parameters.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use crate::{Error, Params};
+
+pub const MAGIC: &[u8; 8] = b"SYNCMRS\0";
+pub const VERSION: u16 = 1;
+
+/// Smer ordering recorded in the header. Only lexicographic is implemented
+pub const ORDER_LEXICOGRAPHIC: u8 = 0;
+
+/// What the values of each record are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    /// Syncmer positions, as from find_syncmers_pos
+    Positions,
+    /// Syncmer hashes, as in a sketch
+    Hashes,
+}
+
+/// Parameters every record in a file was built with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub kind: RecordKind,
+    pub params: Params,
+    /// Hash threshold as u64::MAX / scaled, 1 when all syncmers are kept
+    pub scaled: u64,
+}
+
+impl Header {
+    pub fn new(kind: RecordKind, params: Params, scaled: u64) -> Self {
+        assert!(scaled >= 1);
+        Header {
+            kind,
+            params,
+            scaled,
+        }
+    }
+
+    /// Error unless both files hold the same kind of records built with the same parameters
+    pub fn check_compatible(&self, other: &Header) -> Result<(), Error> {
+        if self.kind != other.kind {
+            return Err(Error::IncompatibleParams(format!(
+                "{:?} vs {:?} records",
+                self.kind, other.kind
+            )));
+        }
+        self.params.check_compatible(&other.params)?;
+        if self.scaled != other.scaled {
+            return Err(Error::IncompatibleParams(format!(
+                "scaled {} vs {}",
+                self.scaled, other.scaled
+            )));
+        }
+        Ok(())
+    }
+
+    fn write<W: Write>(&self, out: &mut W) -> Result<(), Error> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&[match self.kind {
+            RecordKind::Positions => 0,
+            RecordKind::Hashes => 1,
+        }])?;
+        write_params(out, &self.params)?;
+        write_varint(out, self.scaled)?;
+        Ok(())
+    }
+
+    fn read<R: Read>(input: &mut R) -> Result<Self, Error> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::Format("Not a syncmers file (bad magic)".to_string()));
+        }
+
+        let mut version = [0; 2];
+        input.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version == 0 || version > VERSION {
+            return Err(Error::Format(format!(
+                "Unsupported file version {} (this build reads up to {})",
+                version, VERSION
+            )));
+        }
+
+        let kind = match read_u8(input)? {
+            0 => RecordKind::Positions,
+            1 => RecordKind::Hashes,
+            x => return Err(Error::Format(format!("Unknown record kind {}", x))),
+        };
+
+        let params = read_params(input)?;
+        let scaled = read_varint(input)?;
+        if scaled == 0 {
+            return Err(Error::Format("scaled must be at least 1".to_string()));
+        }
+        Ok(Header::new(kind, params, scaled))
+    }
+}
+
+/// k, s, ts, smer ordering, canonical flag and seed, as laid out in the header
+pub(crate) fn write_params<W: Write>(out: &mut W, params: &Params) -> Result<(), Error> {
+    write_varint(out, params.k() as u64)?;
+    write_varint(out, params.s() as u64)?;
+    write_varint(out, params.ts().len() as u64)?;
+    for &t in params.ts() {
+        write_varint(out, t as u64)?;
+    }
+    out.write_all(&[ORDER_LEXICOGRAPHIC, params.canonical() as u8])?;
+    out.write_all(&params.seed().to_le_bytes())?;
+    Ok(())
+}
+
+pub(crate) fn read_params<R: Read>(input: &mut R) -> Result<Params, Error> {
+    let k = read_varint(input)? as usize;
+    let s = read_varint(input)? as usize;
+    let ts = (0..read_varint(input)?)
+        .map(|_| read_varint(input).map(|x| x as usize))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let ordering = read_u8(input)?;
+    if ordering != ORDER_LEXICOGRAPHIC {
+        return Err(Error::Format(format!("Unknown smer ordering {}", ordering)));
+    }
+    let canonical = read_u8(input)? != 0;
+
+    let mut seed = [0; 8];
+    input.read_exact(&mut seed)?;
+    let seed = u64::from_le_bytes(seed);
+
+    Ok(Params::new(k, s, &ts)?
+        .with_seed(seed)
+        .with_canonical(canonical))
+}
+
+/// A named record of sorted values (positions or hashes)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub name: String,
+    pub values: Vec<u64>,
+}
+
+/// Writes a header, then records one at a time
+pub struct SyncmerWriter<W: Write> {
+    out: W,
+    header: Header,
+}
+
+impl SyncmerWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, header: Header) -> Result<Self, Error> {
+        SyncmerWriter::new(BufWriter::new(File::create(path)?), header)
+    }
+}
+
+impl<W: Write> SyncmerWriter<W> {
+    pub fn new(mut out: W, header: Header) -> Result<Self, Error> {
+        header.write(&mut out)?;
+        Ok(SyncmerWriter { out, header })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Write a record. values must be sorted (ascending)
+    pub fn write_record(&mut self, name: &str, values: &[u64]) -> Result<(), Error> {
+        if values.windows(2).any(|x| x[0] > x[1]) {
+            return Err(Error::Format(format!(
+                "Values of record {} are not sorted",
+                name
+            )));
+        }
+
+        write_varint(&mut self.out, name.len() as u64)?;
+        self.out.write_all(name.as_bytes())?;
+        write_varint(&mut self.out, values.len() as u64)?;
+        let mut last = 0;
+        for &value in values {
+            write_varint(&mut self.out, value - last)?;
+            last = value;
+        }
+        Ok(())
+    }
+
+    /// Write syncmer positions, as returned by find_syncmers_pos
+    pub fn write_positions(&mut self, name: &str, positions: &[usize]) -> Result<(), Error> {
+        let values = positions.iter().map(|&x| x as u64).collect::<Vec<_>>();
+        self.write_record(name, &values)
+    }
+
+    /// Flush and return the underlying writer
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Reads the header, then iterates over records
+pub struct SyncmerReader<R: Read> {
+    input: R,
+    header: Header,
+}
+
+impl SyncmerReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        SyncmerReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> SyncmerReader<R> {
+    pub fn new(mut input: R) -> Result<Self, Error> {
+        let header = Header::read(&mut input)?;
+        Ok(SyncmerReader { input, header })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    fn next_record(&mut self) -> Result<Option<Record>, Error> {
+        // A clean end of file between records ends the file
+        let mut first = [0; 1];
+        loop {
+            match self.input.read(&mut first) {
+                Ok(0) => return Ok(None),
+                Ok(_) => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        // Only as many bytes as the file has are allocated, whatever the length says
+        let len = read_varint(&mut (&first[..]).chain(&mut self.input))?;
+        let mut name = Vec::new();
+        (&mut self.input).take(len).read_to_end(&mut name)?;
+        if name.len() as u64 != len {
+            return Err(Error::Format("Truncated record name".to_string()));
+        }
+        let name = String::from_utf8(name)
+            .map_err(|_| Error::Format("Record name is not UTF-8".to_string()))?;
+
+        let count = read_varint(&mut self.input)? as usize;
+        let mut values = Vec::with_capacity(count.min(1 << 20));
+        let mut last = 0u64;
+        for _ in 0..count {
+            last = last
+                .checked_add(read_varint(&mut self.input)?)
+                .ok_or_else(|| Error::Format("Value overflow".to_string()))?;
+            values.push(last);
+        }
+        Ok(Some(Record { name, values }))
+    }
+}
+
+impl<R: Read> Iterator for SyncmerReader<R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+/// Open several files for reading together, refusing any built with different parameters
+/// than the first
+pub fn open_compatible<P: AsRef<Path>>(
+    paths: &[P],
+) -> Result<Vec<SyncmerReader<BufReader<File>>>, Error> {
+    let readers = paths
+        .iter()
+        .map(SyncmerReader::open)
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(first) = readers.first() {
+        for (reader, path) in readers.iter().zip(paths.iter()).skip(1) {
+            first
+                .header()
+                .check_compatible(reader.header())
+                .map_err(|e| match e {
+                    Error::IncompatibleParams(msg) => {
+                        Error::IncompatibleParams(format!("{}: {}", path.as_ref().display(), msg))
+                    }
+                    e => e,
+                })?;
+        }
+    }
+    Ok(readers)
+}
+
+/// Whether the file at path starts with the syncmers [`MAGIC`]
+pub fn is_syncmers_file<P: AsRef<Path>>(path: P) -> Result<bool, Error> {
+    let mut magic = [0; 8];
+    match File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == MAGIC),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub(crate) fn read_u8<R: Read>(input: &mut R) -> Result<u8, Error> {
+    let mut buf = [0; 1];
+    input.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+/// LEB128 unsigned varint
+pub(crate) fn write_varint<W: Write>(out: &mut W, mut value: u64) -> Result<(), Error> {
+    let mut buf = [0; 10];
+    let mut i = 0;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf[i] = byte;
+            i += 1;
+            break;
+        }
+        buf[i] = byte | 0x80;
+        i += 1;
+    }
+    out.write_all(&buf[..i])?;
+    Ok(())
+}
+
+pub(crate) fn read_varint<R: Read>(input: &mut R) -> Result<u64, Error> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(input)?;
+        // The 10th byte only has room for the top bit
+        if shift == 63 && byte & 0x7f > 1 {
+            return Err(Error::Format("Varint overflows 64 bits".to_string()));
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::Format("Varint is too long".to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::find_syncmers_pos;
+
+    #[test]
+    pub fn test_roundtrip_and_compatibility() {
+        let params = Params::new(5, 2, &[2]).unwrap();
+        let header = Header::new(RecordKind::Positions, params.clone(), 1);
+        let positions = find_syncmers_pos(5, 2, &[2], b"CCAGTGTTTACGG");
+
+        let mut writer = SyncmerWriter::new(Vec::new(), header.clone()).unwrap();
+        writer.write_positions("fig1b", &positions).unwrap();
+        writer.write_record("big", &[0, 1, u64::MAX]).unwrap();
+        writer.write_record("empty", &[]).unwrap();
+        assert!(writer.write_record("unsorted", &[2, 1]).is_err());
+        let bytes = writer.finish().unwrap();
+
+        let reader = SyncmerReader::new(&bytes[..]).unwrap();
+        assert_eq!(reader.header(), &header);
+        let records = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].name, "fig1b");
+        assert_eq!(records[0].values, vec![0, 7]);
+        assert_eq!(records[1].values, vec![0, 1, u64::MAX]);
+        assert!(records[2].values.is_empty());
+
+        let other = Header::new(RecordKind::Positions, params.with_canonical(true), 1);
+        assert!(header.check_compatible(&other).is_err());
+
+        // Newer versions are refused
+        let mut newer = bytes.clone();
+        newer[8] = 99;
+        assert!(SyncmerReader::new(&newer[..]).is_err());
+    }
+
+    #[test]
+    pub fn test_corrupt_records() {
+        let header = Header::new(RecordKind::Positions, Params::new(5, 2, &[2]).unwrap(), 1);
+        let empty = SyncmerWriter::new(Vec::new(), header)
+            .unwrap()
+            .finish()
+            .unwrap();
+        let read = |record: &[u8]| {
+            SyncmerReader::new(&[&empty[..], record].concat()[..])
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        // A name length far beyond the file is not allocated
+        let mut huge = Vec::new();
+        write_varint(&mut huge, u64::MAX).unwrap();
+        huge.extend(b"abc");
+        assert!(matches!(read(&huge), Err(Error::Format(_))));
+
+        // 64 bits fit in 10 bytes, with only the top bit in the last one
+        let mut max = Vec::new();
+        write_varint(&mut max, u64::MAX).unwrap();
+        assert_eq!(max.len(), 10);
+        assert_eq!(read_varint(&mut &max[..]).unwrap(), u64::MAX);
+        max[9] = 0x02;
+        assert!(read_varint(&mut &max[..]).is_err());
+        assert!(read_varint(&mut &[0x80; 11][..]).is_err());
+    }
+}
@@ -0,0 +1,453 @@
+//! Syncmer-space de Bruijn graphs, after rust-mdbg's minimizer-space de Bruijn graphs
+//!
+//! Each read is turned into its sequence of canonical syncmer hashes, and every ℓ consecutive
+//! syncmers form a node, so the "letters" of the graph are syncmers rather than bases. A node
+//! and its reverse complement are the same node: its tuple is stored in the orientation that
+//! compares smaller, and a read sees it on the Forward or Reverse strand. Edges join nodes
+//! whose tuples overlap by ℓ - 1 syncmers. Each node keeps the bases of the first read it was
+//! seen in, which are used to spell unitigs and the overlaps of GFA links.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use rayon::prelude::*;
+
+use crate::fasta::Record;
+use crate::{revcomp, Error, Params, Strand};
+
+/// Graph construction parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphParams {
+    /// Number of consecutive syncmers per node (ℓ)
+    pub order: usize,
+    /// Nodes seen in fewer reads are dropped, as likely sequencing errors
+    pub min_abundance: usize,
+}
+
+impl Default for GraphParams {
+    fn default() -> Self {
+        GraphParams {
+            order: 5,
+            min_abundance: 2,
+        }
+    }
+}
+
+/// A node on one of its strands
+pub type Handle = (usize, Strand);
+
+fn flip((node, strand): Handle) -> Handle {
+    match strand {
+        Strand::Forward => (node, Strand::Reverse),
+        Strand::Reverse => (node, Strand::Forward),
+    }
+}
+
+/// Index of the outgoing edges of a handle
+fn side((node, strand): Handle) -> usize {
+    2 * node + (strand == Strand::Reverse) as usize
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    /// Syncmer hashes, in the canonical orientation
+    syncmers: Vec<u64>,
+    /// Bases from the first syncmer to the end of the last one, in the canonical orientation
+    seq: Vec<u8>,
+    /// Start of each syncmer in seq
+    offsets: Vec<usize>,
+    /// Number of reads the node was seen in
+    abundance: usize,
+    /// Last read the node was seen in, so repeats within a read count once
+    last_read: usize,
+}
+
+/// A maximal non-branching path of the graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unitig {
+    pub nodes: Vec<Handle>,
+    pub seq: Vec<u8>,
+}
+
+/// A syncmer-space de Bruijn graph
+#[derive(Debug, Clone)]
+pub struct Graph {
+    params: Params,
+    order: usize,
+    nodes: Vec<Node>,
+    /// Successors of each handle, see [`side`]
+    edges: Vec<Vec<Handle>>,
+}
+
+impl Graph {
+    /// Build the graph of a set of reads. Syncmers are always canonical
+    pub fn build(params: &Params, reads: &[Record], graph_params: &GraphParams) -> Self {
+        let params = params.clone().with_canonical(true);
+        let order = graph_params.order.max(2);
+        let k = params.k();
+
+        let syncmers = reads
+            .par_iter()
+            .map(|x| params.syncmers(&x.seq))
+            .collect::<Vec<_>>();
+
+        let mut ids = HashMap::new();
+        let mut nodes: Vec<Node> = Vec::new();
+        for (i, (read, syncmers)) in reads.iter().zip(&syncmers).enumerate() {
+            for window in syncmers.windows(order) {
+                let forward = window.iter().map(|x| x.hash).collect::<Vec<_>>();
+                let reverse = forward.iter().rev().copied().collect::<Vec<_>>();
+                let (tuple, strand) = if reverse < forward {
+                    (reverse, Strand::Reverse)
+                } else {
+                    (forward, Strand::Forward)
+                };
+
+                let id = *ids.entry(tuple).or_insert_with_key(|tuple| {
+                    let start = window[0].pos;
+                    let end = window[order - 1].pos + k;
+                    let mut seq = read.seq[start..end].to_vec();
+                    let mut offsets = window.iter().map(|x| x.pos - start).collect::<Vec<_>>();
+                    if strand == Strand::Reverse {
+                        revcomp(&mut seq);
+                        offsets = offsets.iter().rev().map(|x| end - start - k - x).collect();
+                    }
+                    nodes.push(Node {
+                        syncmers: tuple.clone(),
+                        seq,
+                        offsets,
+                        abundance: 0,
+                        last_read: usize::MAX,
+                    });
+                    nodes.len() - 1
+                });
+                if nodes[id].last_read != i {
+                    nodes[id].abundance += 1;
+                    nodes[id].last_read = i;
+                }
+            }
+        }
+        nodes.retain(|x| x.abundance >= graph_params.min_abundance);
+
+        // Link every oriented (ℓ - 1)-suffix to the oriented nodes starting with it
+        let oriented = |node: &Node, strand: Strand| -> Vec<u64> {
+            match strand {
+                Strand::Forward => node.syncmers.clone(),
+                Strand::Reverse => node.syncmers.iter().rev().copied().collect(),
+            }
+        };
+        let mut prefixes: HashMap<Vec<u64>, Vec<Handle>> = HashMap::new();
+        for (i, node) in nodes.iter().enumerate() {
+            for strand in [Strand::Forward, Strand::Reverse] {
+                let mut tuple = oriented(node, strand);
+                tuple.pop();
+                prefixes.entry(tuple).or_default().push((i, strand));
+            }
+        }
+        let mut edges = vec![Vec::new(); 2 * nodes.len()];
+        for (i, node) in nodes.iter().enumerate() {
+            for strand in [Strand::Forward, Strand::Reverse] {
+                let suffix = &oriented(node, strand)[1..];
+                if let Some(next) = prefixes.get(suffix) {
+                    edges[side((i, strand))] = next.clone();
+                }
+            }
+        }
+
+        Graph {
+            params,
+            order,
+            nodes,
+            edges,
+        }
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Number of nodes
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Number of edges, counting an edge and its reverse complement once
+    pub fn edge_count(&self) -> usize {
+        let mut count = 0;
+        for (i, next) in self.edges.iter().enumerate() {
+            let from = (
+                i / 2,
+                if i % 2 == 0 {
+                    Strand::Forward
+                } else {
+                    Strand::Reverse
+                },
+            );
+            // (u, v) and (flip v, flip u) are the same edge
+            count += next.iter().filter(|&&to| from <= flip(to)).count();
+        }
+        count
+    }
+
+    /// Number of reads a node was seen in
+    pub fn abundance(&self, node: usize) -> usize {
+        self.nodes[node].abundance
+    }
+
+    /// Syncmer hashes of a node on a strand
+    pub fn syncmers(&self, (node, strand): Handle) -> Vec<u64> {
+        let syncmers = &self.nodes[node].syncmers;
+        match strand {
+            Strand::Forward => syncmers.clone(),
+            Strand::Reverse => syncmers.iter().rev().copied().collect(),
+        }
+    }
+
+    /// Bases of a node on a strand
+    pub fn sequence(&self, (node, strand): Handle) -> Vec<u8> {
+        let mut seq = self.nodes[node].seq.clone();
+        if strand == Strand::Reverse {
+            revcomp(&mut seq);
+        }
+        seq
+    }
+
+    pub fn successors(&self, handle: Handle) -> &[Handle] {
+        &self.edges[side(handle)]
+    }
+
+    pub fn predecessors(&self, handle: Handle) -> impl Iterator<Item = Handle> + '_ {
+        self.successors(flip(handle)).iter().map(|&x| flip(x))
+    }
+
+    /// Bases a handle shares with its predecessors: from the start of the handle to the end
+    /// of its (ℓ - 1)th syncmer
+    fn overlap(&self, (node, strand): Handle) -> usize {
+        let node = &self.nodes[node];
+        let k = self.params.k();
+        match strand {
+            Strand::Forward => node.offsets[self.order - 2] + k,
+            Strand::Reverse => node.seq.len() - node.offsets[1],
+        }
+    }
+
+    /// The next handle of a unitig, if the path doesn't branch
+    fn unique_successor(&self, handle: Handle) -> Option<Handle> {
+        match self.successors(handle) {
+            &[next] if self.successors(flip(next)).len() == 1 => Some(next),
+            _ => None,
+        }
+    }
+
+    /// Compact the graph into unitigs, covering each node once
+    pub fn unitigs(&self) -> Vec<Unitig> {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut unitigs = Vec::new();
+        for start in 0..self.nodes.len() {
+            if visited[start] {
+                continue;
+            }
+            visited[start] = true;
+            let mut extend = |mut handle: Handle| {
+                let mut path = Vec::new();
+                while let Some(next) = self.unique_successor(handle) {
+                    if visited[next.0] {
+                        break;
+                    }
+                    visited[next.0] = true;
+                    path.push(next);
+                    handle = next;
+                }
+                path
+            };
+            let backward = extend((start, Strand::Reverse));
+            let forward = extend((start, Strand::Forward));
+
+            let nodes = backward
+                .into_iter()
+                .rev()
+                .map(flip)
+                .chain(std::iter::once((start, Strand::Forward)))
+                .chain(forward)
+                .collect::<Vec<_>>();
+            let mut seq = self.sequence(nodes[0]);
+            for &handle in &nodes[1..] {
+                seq.extend_from_slice(&self.sequence(handle)[self.overlap(handle)..]);
+            }
+            unitigs.push(Unitig { nodes, seq });
+        }
+        unitigs
+    }
+
+    /// Write the unitigs as GFA1 segments (with LN and KC tags, KC being the summed node
+    /// abundance), joined by links with their overlaps in bases
+    pub fn write_gfa<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        let unitigs = self.unitigs();
+        writeln!(out, "H\tVN:Z:1.0")?;
+
+        // Where each node starts or ends a unitig
+        let mut ends = HashMap::new();
+        for (i, unitig) in unitigs.iter().enumerate() {
+            let (first, last) = (unitig.nodes[0], *unitig.nodes.last().unwrap());
+            ends.insert(first, (i, Strand::Forward));
+            ends.insert(flip(last), (i, Strand::Reverse));
+
+            let abundance = unitig
+                .nodes
+                .iter()
+                .map(|x| self.nodes[x.0].abundance)
+                .sum::<usize>();
+            write!(out, "S\tu{}\t", i)?;
+            out.write_all(&unitig.seq)?;
+            writeln!(out, "\tLN:i:{}\tKC:i:{}", unitig.seq.len(), abundance)?;
+        }
+
+        let sign = |strand: Strand| match strand {
+            Strand::Forward => '+',
+            Strand::Reverse => '-',
+        };
+        for (i, unitig) in unitigs.iter().enumerate() {
+            let (first, last) = (unitig.nodes[0], *unitig.nodes.last().unwrap());
+            for (from, strand) in [(last, Strand::Forward), (flip(first), Strand::Reverse)] {
+                for &next in self.successors(from) {
+                    // Unitigs are maximal, so links only lead to their ends
+                    let Some(&(j, next_strand)) = ends.get(&next) else {
+                        continue;
+                    };
+                    // Each link once, from the smaller of its two orientations
+                    if (i, strand) > flip((j, next_strand)) {
+                        continue;
+                    }
+                    writeln!(
+                        out,
+                        "L\tu{}\t{}\tu{}\t{}\t{}M",
+                        i,
+                        sign(strand),
+                        j,
+                        sign(next_strand),
+                        self.overlap(next)
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::random_seq;
+
+    /// Error-free reads tiling seq every step bases, every other one reverse complemented
+    fn reads(seq: &[u8], len: usize, step: usize) -> Vec<Record> {
+        (0..)
+            .map(|i| i * step)
+            .take_while(|&start| start + len <= seq.len())
+            .enumerate()
+            .map(|(i, start)| {
+                let mut read = seq[start..start + len].to_vec();
+                if i % 2 == 1 {
+                    revcomp(&mut read);
+                }
+                Record {
+                    id: format!("read{}", i),
+                    seq: read,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn test_graph_linear() {
+        let genome = random_seq(20_000, 47);
+        let params = Params::new(15, 7, &[4]).unwrap();
+        let graph = Graph::build(
+            &params,
+            &reads(&genome, 2_000, 250),
+            &GraphParams::default(),
+        );
+        assert!(!graph.is_empty());
+        assert_eq!(graph.edge_count(), graph.len() - 1);
+
+        // A single unitig spelling the genome on one of its strands
+        let unitigs = graph.unitigs();
+        assert_eq!(unitigs.len(), 1);
+        let mut seq = unitigs[0].seq.clone();
+        if !genome.windows(seq.len()).any(|x| x == seq) {
+            revcomp(&mut seq);
+        }
+        let start = genome.windows(seq.len()).position(|x| x == seq).unwrap();
+        assert!(start < 500 && start + seq.len() > genome.len() - 500);
+
+        for pair in unitigs[0].nodes.windows(2) {
+            assert_eq!(graph.successors(pair[0]), &[pair[1]]);
+            assert_eq!(
+                graph.syncmers(pair[0])[1..],
+                graph.syncmers(pair[1])[..graph.order() - 1]
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_graph_repeat_gfa() {
+        // a R b R c: the repeat R is collapsed into one unitig joined to the unique parts
+        let part = |seed| random_seq(3_000, seed);
+        let repeat = random_seq(1_500, 53);
+        let genome = [part(59), repeat.clone(), part(61), repeat, part(67)].concat();
+        let params = Params::new(15, 7, &[4]).unwrap();
+        let graph = Graph::build(
+            &params,
+            &reads(&genome, 2_500, 200),
+            &GraphParams::default(),
+        );
+
+        let unitigs = graph.unitigs();
+        assert!(unitigs.len() >= 4);
+        let nodes = unitigs.iter().map(|x| x.nodes.len()).sum::<usize>();
+        assert_eq!(nodes, graph.len());
+
+        let mut gfa = Vec::new();
+        graph.write_gfa(&mut gfa).unwrap();
+        let gfa = String::from_utf8(gfa).unwrap();
+        let segments = gfa.lines().filter(|x| x.starts_with("S\t")).count();
+        let links = gfa
+            .lines()
+            .filter(|x| x.starts_with("L\t"))
+            .map(|x| x.split('\t').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(segments, unitigs.len());
+        assert!(gfa.starts_with("H\tVN:Z:1.0\n"));
+        assert!(links.len() >= 4);
+        for link in &links {
+            let overlap = link[5].trim_end_matches('M').parse::<usize>().unwrap();
+            assert!((15..2_000).contains(&overlap));
+        }
+
+        // Nodes seen once are dropped
+        let reads = reads(&genome[..6_000], 2_500, 2_500);
+        let strict = Graph::build(&params, &reads, &GraphParams::default());
+        assert!(strict.is_empty());
+
+        // Repeats within a read count once
+        let tandem = vec![Record {
+            id: "tandem".to_string(),
+            seq: random_seq(1_500, 53).repeat(4),
+        }];
+        assert!(Graph::build(&params, &tandem, &GraphParams::default()).is_empty());
+        let all = GraphParams {
+            min_abundance: 1,
+            ..Default::default()
+        };
+        let single = Graph::build(&params, &tandem, &all);
+        assert!(!single.is_empty() && (0..single.len()).all(|x| single.abundance(x) == 1));
+    }
+}
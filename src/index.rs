@@ -0,0 +1,697 @@
+//! Syncmer positional index (hash -> record, position, strand) for seeding
+//!
+//! Syncmer hashes are kept in a sorted array with an offset table into a flat array of packed
+//! occurrences, so a lookup is a binary search and the layout is three plain u64 arrays.
+//!
+//! The same arrays are written to disk by [`Index::save`], 8-byte aligned, and
+//! [`Index::open`] memory-maps them: loading is near-instant, nothing is deserialized, and
+//! processes opening the same file share its pages.
+
+use std::fs::File;
+use std::io::{BufWriter, ErrorKind, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+use crate::fasta::{FastaReader, Record};
+use crate::format::{read_params, read_varint, write_params, write_varint};
+use crate::{Error, Params, Strand, Syncmer};
+
+/// Magic number at the start of index files
+pub const INDEX_MAGIC: &[u8; 8] = b"SYNCIDX\0";
+pub const INDEX_VERSION: u16 = 1;
+
+/// Where a syncmer occurs in the reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Occurrence {
+    /// Index of the reference record
+    pub record: u32,
+    /// Start of the kmer on the forward strand of the record
+    pub pos: u32,
+    pub strand: Strand,
+}
+
+impl Occurrence {
+    /// record (high 31 bits), strand (1 bit), pos (low 32 bits)
+    #[inline]
+    fn pack(&self) -> u64 {
+        ((self.record as u64) << 33)
+            | ((self.strand == Strand::Reverse) as u64) << 32
+            | self.pos as u64
+    }
+
+    #[inline]
+    fn unpack(x: u64) -> Self {
+        Occurrence {
+            record: (x >> 33) as u32,
+            pos: x as u32,
+            strand: if x & (1 << 32) != 0 {
+                Strand::Reverse
+            } else {
+                Strand::Forward
+            },
+        }
+    }
+}
+
+/// Occurrences of one syncmer, decoded on access
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Occurrences<'a> {
+    packed: &'a [u64],
+}
+
+impl<'a> Occurrences<'a> {
+    pub fn len(&self) -> usize {
+        self.packed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packed.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> Option<Occurrence> {
+        self.packed.get(i).map(|&x| Occurrence::unpack(x))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Occurrence> + 'a {
+        self.packed.iter().map(|&x| Occurrence::unpack(x))
+    }
+}
+
+/// A syncmer of the query and its occurrences in the reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryHit<'a> {
+    pub query: Syncmer,
+    pub occurrences: Occurrences<'a>,
+}
+
+#[derive(Debug)]
+enum Storage {
+    Owned {
+        hashes: Vec<u64>,
+        offsets: Vec<u64>,
+        occurrences: Vec<u64>,
+    },
+    Mapped {
+        mmap: Mmap,
+        hashes: Range<usize>,
+        offsets: Range<usize>,
+        occurrences: Range<usize>,
+    },
+}
+
+/// Sorted-array syncmer index over a set of reference records
+#[derive(Debug)]
+pub struct Index {
+    params: Params,
+    names: Vec<String>,
+    lengths: Vec<usize>,
+    storage: Storage,
+    // Syncmers with more occurrences are masked from queries
+    max_occurrences: Option<usize>,
+}
+
+/// Occurrence cutoff above which a syncmer is masked from queries, like minimap2's `-f`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cutoff {
+    /// Mask syncmers with more than this many occurrences
+    Occurrences(usize),
+    /// Mask the most frequent fraction (0..=1) of distinct syncmers
+    Fraction(f64),
+}
+
+/// What an occurrence cutoff masks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MaskStats {
+    /// Syncmers with more occurrences than this are masked (None: nothing is)
+    pub max_occurrences: Option<usize>,
+    pub masked_syncmers: usize,
+    pub masked_occurrences: usize,
+    pub total_syncmers: usize,
+    pub total_occurrences: usize,
+}
+
+impl Index {
+    /// Index reference records, extracting their syncmers in parallel
+    ///
+    /// ```rust
+    /// # use syncmers::fasta::Record;
+    /// # use syncmers::{Index, Params};
+    /// let params = Params::new(5, 2, &[2]).unwrap();
+    /// let reference = vec![Record { id: "ref".to_string(), seq: b"CCAGTGTTTACGG".to_vec() }];
+    /// let index = Index::build(params, &reference);
+    /// let hits = index.query(b"GGTTTACGA");
+    /// assert!(hits.len() == 1 && hits[0].occurrences.get(0).unwrap().pos == 7);
+    /// ```
+    pub fn build(params: Params, records: &[Record]) -> Self {
+        assert!(records.len() < 1 << 31);
+        assert!(records.iter().all(|x| x.seq.len() <= u32::MAX as usize));
+
+        let mut entries = records
+            .par_iter()
+            .enumerate()
+            .flat_map_iter(|(record, x)| {
+                params.syncmers(&x.seq).into_iter().map(move |syncmer| {
+                    let occurrence = Occurrence {
+                        record: record as u32,
+                        pos: syncmer.pos as u32,
+                        strand: syncmer.strand,
+                    };
+                    (syncmer.hash, occurrence.pack())
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.par_sort_unstable();
+
+        let mut hashes = Vec::new();
+        let mut offsets = Vec::new();
+        let mut occurrences = Vec::with_capacity(entries.len());
+        for (hash, occurrence) in entries {
+            if hashes.last() != Some(&hash) {
+                hashes.push(hash);
+                offsets.push(occurrences.len() as u64);
+            }
+            occurrences.push(occurrence);
+        }
+        offsets.push(occurrences.len() as u64);
+
+        Index {
+            params,
+            names: records.iter().map(|x| x.id.clone()).collect(),
+            lengths: records.iter().map(|x| x.seq.len()).collect(),
+            storage: Storage::Owned {
+                hashes,
+                offsets,
+                occurrences,
+            },
+            max_occurrences: None,
+        }
+    }
+
+    /// Index every record of a FASTA file
+    pub fn from_fasta<P: AsRef<Path>>(params: Params, path: P) -> Result<Self, Error> {
+        let records = FastaReader::from_path(path)?.collect::<Result<Vec<_>, _>>()?;
+        Ok(Index::build(params, &records))
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// Error unless this index was built with params
+    pub fn check_params(&self, params: &Params) -> Result<(), Error> {
+        self.params.check_compatible(params)
+    }
+
+    /// Names of the reference records, indexed by [`Occurrence::record`]
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Lengths of the reference records
+    pub fn lengths(&self) -> &[usize] {
+        &self.lengths
+    }
+
+    /// Whether the arrays are memory-mapped from a file
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.storage, Storage::Mapped { .. })
+    }
+
+    fn hashes(&self) -> &[u64] {
+        match &self.storage {
+            Storage::Owned { hashes, .. } => hashes,
+            Storage::Mapped { mmap, hashes, .. } => as_u64s(&mmap[hashes.clone()]),
+        }
+    }
+
+    fn offsets(&self) -> &[u64] {
+        match &self.storage {
+            Storage::Owned { offsets, .. } => offsets,
+            Storage::Mapped { mmap, offsets, .. } => as_u64s(&mmap[offsets.clone()]),
+        }
+    }
+
+    fn packed_occurrences(&self) -> &[u64] {
+        match &self.storage {
+            Storage::Owned { occurrences, .. } => occurrences,
+            Storage::Mapped {
+                mmap, occurrences, ..
+            } => as_u64s(&mmap[occurrences.clone()]),
+        }
+    }
+
+    /// Number of distinct syncmers
+    pub fn len(&self) -> usize {
+        self.hashes().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes().is_empty()
+    }
+
+    /// Total number of syncmer occurrences
+    pub fn occurrences(&self) -> usize {
+        self.packed_occurrences().len()
+    }
+
+    /// Mask syncmers above cutoff from [`Index::query`]. The index itself is unchanged, so
+    /// [`Index::get`] and [`Index::query_with_max_occurrences`] still see them
+    pub fn with_cutoff(mut self, cutoff: Cutoff) -> Self {
+        self.set_cutoff(Some(cutoff));
+        self
+    }
+
+    /// Set (or with None, clear) the occurrence cutoff, returning what it masks
+    pub fn set_cutoff(&mut self, cutoff: Option<Cutoff>) -> MaskStats {
+        self.max_occurrences = match cutoff {
+            None => None,
+            Some(Cutoff::Occurrences(n)) => Some(n),
+            Some(Cutoff::Fraction(f)) => {
+                assert!((0.0..=1.0).contains(&f), "fraction must be in 0..=1");
+                let mut counts = self.counts().collect::<Vec<_>>();
+                counts.sort_unstable();
+                // Largest count below the top f of syncmers. Ties with it stay unmasked
+                let masked = (f * counts.len() as f64).round() as usize;
+                if masked == 0 {
+                    None
+                } else {
+                    Some(
+                        counts
+                            .len()
+                            .checked_sub(masked + 1)
+                            .map_or(0, |i| counts[i]),
+                    )
+                }
+            }
+        };
+        self.mask_stats()
+    }
+
+    /// Syncmers with more occurrences than this are masked from queries
+    pub fn max_occurrences(&self) -> Option<usize> {
+        self.max_occurrences
+    }
+
+    /// What the current cutoff masks
+    pub fn mask_stats(&self) -> MaskStats {
+        let mut stats = MaskStats {
+            max_occurrences: self.max_occurrences,
+            total_syncmers: self.len(),
+            total_occurrences: self.occurrences(),
+            ..Default::default()
+        };
+        if let Some(max) = self.max_occurrences {
+            for count in self.counts().filter(|&x| x > max) {
+                stats.masked_syncmers += 1;
+                stats.masked_occurrences += count;
+            }
+        }
+        stats
+    }
+
+    /// Number of occurrences of each distinct syncmer
+    fn counts(&self) -> impl Iterator<Item = usize> + '_ {
+        self.offsets().windows(2).map(|x| (x[1] - x[0]) as usize)
+    }
+
+    /// Occurrences of a syncmer hash, empty if it isn't in the reference
+    pub fn get(&self, hash: u64) -> Occurrences<'_> {
+        let packed = match self.hashes().binary_search(&hash) {
+            Ok(i) => {
+                let offsets = self.offsets();
+                &self.packed_occurrences()[offsets[i] as usize..offsets[i + 1] as usize]
+            }
+            Err(_) => &[],
+        };
+        Occurrences { packed }
+    }
+
+    /// Find the syncmers of a query sequence (with the index's params) and their occurrences.
+    /// Query syncmers not found in the reference, or masked by the cutoff, are left out
+    pub fn query(&self, seq: &[u8]) -> Vec<QueryHit<'_>> {
+        self.query_with_max_occurrences(seq, self.max_occurrences)
+    }
+
+    /// [`Index::query`] with a different occurrence cutoff, e.g. None to see repeats in
+    /// sensitive runs
+    pub fn query_with_max_occurrences(
+        &self,
+        seq: &[u8],
+        max_occurrences: Option<usize>,
+    ) -> Vec<QueryHit<'_>> {
+        let max = max_occurrences.unwrap_or(usize::MAX);
+        self.params
+            .syncmers(seq)
+            .into_iter()
+            .filter_map(|query| {
+                let occurrences = self.get(query.hash);
+                if occurrences.is_empty() || occurrences.len() > max {
+                    None
+                } else {
+                    Some(QueryHit { query, occurrences })
+                }
+            })
+            .collect()
+    }
+
+    /// Write the index to a single file that [`Index::open`] can memory-map
+    ///
+    /// Layout: magic, version (u16 LE), params (as in [`crate::format`]), record count,
+    /// names and lengths (varints), zero padding to a multiple of 8 bytes, then the hash count
+    /// and occurrence count (u64 LE) followed by the hashes, offsets and packed occurrences
+    /// arrays (u64 LE)
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut out = BufWriter::new(File::create(path)?);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(INDEX_MAGIC);
+        header.extend_from_slice(&INDEX_VERSION.to_le_bytes());
+        write_params(&mut header, &self.params)?;
+        write_varint(&mut header, self.names.len() as u64)?;
+        for (name, &length) in self.names.iter().zip(self.lengths.iter()) {
+            write_varint(&mut header, name.len() as u64)?;
+            header.extend_from_slice(name.as_bytes());
+            write_varint(&mut header, length as u64)?;
+        }
+        header.resize(header.len().next_multiple_of(8), 0);
+        out.write_all(&header)?;
+
+        out.write_all(&(self.len() as u64).to_le_bytes())?;
+        out.write_all(&(self.occurrences() as u64).to_le_bytes())?;
+        for array in [self.hashes(), self.offsets(), self.packed_occurrences()] {
+            for x in array {
+                out.write_all(&x.to_le_bytes())?;
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Memory-map an index written by [`Index::save`]. The arrays are checked in one pass (offsets
+    /// in order and within the occurrences, records among the names), so a corrupt file is an
+    /// [`Error::Format`] rather than a panic on lookup
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        if cfg!(target_endian = "big") {
+            return Err(Error::Format(
+                "Index files are little endian and can't be mapped on this platform".to_string(),
+            ));
+        }
+
+        let file = File::open(path)?;
+        // Safety: the file must not be modified while mapped. Index files are written once
+        // and then only read
+        let mmap = unsafe { Mmap::map(&file)? };
+        let truncated = || Error::Format("Index file is truncated".to_string());
+        let eof_is_truncated = |e: Error| match e {
+            Error::Io(e) if e.kind() == ErrorKind::UnexpectedEof => truncated(),
+            e => e,
+        };
+
+        let mut input = &mmap[..];
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic).map_err(|_| truncated())?;
+        if &magic != INDEX_MAGIC {
+            return Err(Error::Format("Not a syncmers index file".to_string()));
+        }
+        let mut version = [0; 2];
+        input.read_exact(&mut version).map_err(|_| truncated())?;
+        let version = u16::from_le_bytes(version);
+        if version == 0 || version > INDEX_VERSION {
+            return Err(Error::Format(format!(
+                "Unsupported index version {} (this build reads up to {})",
+                version, INDEX_VERSION
+            )));
+        }
+
+        let params = read_params(&mut input).map_err(eof_is_truncated)?;
+        let records = read_varint(&mut input).map_err(eof_is_truncated)?;
+        // Every record takes at least 2 bytes
+        if records > input.len() as u64 / 2 {
+            return Err(truncated());
+        }
+        let mut names = Vec::with_capacity(records as usize);
+        let mut lengths = Vec::with_capacity(records as usize);
+        for _ in 0..records {
+            let len = read_varint(&mut input).map_err(eof_is_truncated)?;
+            if len > input.len() as u64 {
+                return Err(truncated());
+            }
+            let (name, rest) = input.split_at(len as usize);
+            input = rest;
+            names.push(
+                String::from_utf8(name.to_vec())
+                    .map_err(|_| Error::Format("Record name is not UTF-8".to_string()))?,
+            );
+            lengths.push(read_varint(&mut input).map_err(eof_is_truncated)? as usize);
+        }
+
+        let start = (mmap.len() - input.len()).next_multiple_of(8);
+        let counts = mmap
+            .get(start..start.saturating_add(16))
+            .ok_or_else(truncated)?;
+        let n_hashes = u64::from_le_bytes(counts[..8].try_into().unwrap());
+        let n_occurrences = u64::from_le_bytes(counts[8..].try_into().unwrap());
+
+        // Sections of n u64s, which must end exactly at the end of the file
+        let section = |start: usize, n: u64| {
+            let end = usize::try_from(n)
+                .ok()
+                .and_then(|n| n.checked_mul(8))
+                .and_then(|len| start.checked_add(len))
+                .filter(|&end| end <= mmap.len())
+                .ok_or_else(|| {
+                    Error::Format("Index file size doesn't match its header".to_string())
+                })?;
+            Ok::<_, Error>(start..end)
+        };
+        let hashes = section(start + 16, n_hashes)?;
+        let offsets = section(hashes.end, n_hashes + 1)?;
+        let occurrences = section(offsets.end, n_occurrences)?;
+        if occurrences.end != mmap.len() {
+            return Err(Error::Format(
+                "Index file size doesn't match its header".to_string(),
+            ));
+        }
+
+        let offset_values = as_u64s(&mmap[offsets.clone()]);
+        if offset_values[0] != 0
+            || offset_values[n_hashes as usize] != n_occurrences
+            || offset_values.windows(2).any(|x| x[0] > x[1])
+        {
+            return Err(Error::Format("Index offsets are out of order".to_string()));
+        }
+        if as_u64s(&mmap[occurrences.clone()])
+            .par_iter()
+            .any(|&x| Occurrence::unpack(x).record as usize >= names.len())
+        {
+            return Err(Error::Format(
+                "Index occurrence refers to a missing record".to_string(),
+            ));
+        }
+
+        Ok(Index {
+            params,
+            names,
+            lengths,
+            storage: Storage::Mapped {
+                mmap,
+                hashes,
+                offsets,
+                occurrences,
+            },
+            max_occurrences: None,
+        })
+    }
+
+    /// Memory-map an index, refusing it unless it was built with params
+    pub fn open_with_params<P: AsRef<Path>>(path: P, params: &Params) -> Result<Self, Error> {
+        let index = Index::open(path)?;
+        index.check_params(params)?;
+        Ok(index)
+    }
+}
+
+/// Whether path starts with the index magic number
+pub fn is_index_file<P: AsRef<Path>>(path: P) -> Result<bool, Error> {
+    let mut magic = [0; 8];
+    match File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == INDEX_MAGIC),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// View 8-byte aligned little endian bytes as u64s
+fn as_u64s(bytes: &[u8]) -> &[u64] {
+    // Safety: any bit pattern is a valid u64, and the prefix / suffix checks below make sure
+    // the whole slice was aligned and a multiple of 8 bytes long (mmap is page aligned and
+    // sections are at multiples of 8)
+    let (prefix, values, suffix) = unsafe { bytes.align_to::<u64>() };
+    assert!(prefix.is_empty() && suffix.is_empty());
+    values
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::random_seq;
+
+    fn records() -> Vec<Record> {
+        vec![
+            Record {
+                id: "a".to_string(),
+                seq: random_seq(10_000, 3),
+            },
+            Record {
+                id: "b".to_string(),
+                seq: random_seq(10_000, 5),
+            },
+        ]
+    }
+
+    #[test]
+    pub fn test_index_query() {
+        let params = Params::new(21, 11, &[5]).unwrap().with_canonical(true);
+        let records = records();
+        let index = Index::build(params.clone(), &records);
+        let total = records
+            .iter()
+            .map(|x| params.syncmers(&x.seq).len())
+            .sum::<usize>();
+        assert_eq!(index.occurrences(), total);
+
+        // A reverse complemented slice of b is found on the reverse strand
+        let mut query = records[1].seq[2_000..3_000].to_vec();
+        crate::revcomp(&mut query);
+        let hits = index.query(&query);
+        assert!(!hits.is_empty());
+        for hit in hits {
+            let occurrence = hit.occurrences.get(0).unwrap();
+            assert_eq!(occurrence.record, 1);
+            assert_ne!(occurrence.strand, hit.query.strand);
+            // Query position maps back to the reference position on the other strand
+            assert_eq!(occurrence.pos as usize, 2_000 + 1_000 - 21 - hit.query.pos);
+        }
+    }
+
+    #[test]
+    pub fn test_mapped_index() {
+        let params = Params::new(21, 11, &[5]).unwrap().with_canonical(true);
+        let records = records();
+        let index = Index::build(params.clone(), &records);
+
+        let path = std::env::temp_dir().join(format!("syncmers-index-{}.idx", std::process::id()));
+        index.save(&path).unwrap();
+        let mapped = Index::open(&path).unwrap();
+        assert!(mapped.is_mapped());
+        assert!(is_index_file(&path).unwrap());
+        assert_eq!(mapped.params(), index.params());
+        assert_eq!(mapped.names(), index.names());
+        assert_eq!(mapped.lengths(), index.lengths());
+        assert_eq!(mapped.len(), index.len());
+
+        let query = &records[0].seq[500..1_500];
+        assert_eq!(mapped.query(query), index.query(query));
+
+        assert!(Index::open_with_params(&path, &params).is_ok());
+        let other = Params::new(21, 11, &[6]).unwrap().with_canonical(true);
+        assert!(matches!(
+            Index::open_with_params(&path, &other),
+            Err(Error::IncompatibleParams(_))
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    pub fn test_corrupt_index() {
+        let params = Params::new(21, 11, &[5]).unwrap().with_canonical(true);
+        let path =
+            std::env::temp_dir().join(format!("syncmers-corrupt-{}.idx", std::process::id()));
+        let index = Index::build(params, &records());
+        index.save(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let open = |bytes: &[u8]| {
+            std::fs::write(&path, bytes).unwrap();
+            Index::open(&path)
+        };
+        assert!(open(&bytes).is_ok());
+
+        // The two counts, then the hashes, offsets and occurrences
+        let (n_hashes, n_occurrences) = (index.len(), index.occurrences());
+        let counts = bytes.len() - 8 * (2 + 2 * n_hashes + 1 + n_occurrences);
+        let mut corrupt = Vec::new();
+        for (at, value) in [
+            (counts, u64::MAX),
+            (counts, u64::MAX / 8),
+            (counts + 8, u64::MAX / 4),
+            // An offset past the occurrences
+            (counts + 16 + 8 * n_hashes + 8, u64::MAX),
+            // An occurrence of a record that doesn't exist
+            (bytes.len() - 8, 7 << 33),
+        ] {
+            let mut x = bytes.clone();
+            x[at..at + 8].copy_from_slice(&value.to_le_bytes());
+            corrupt.push(x);
+        }
+        corrupt.push(bytes[..bytes.len() - 4].to_vec());
+        for len in 0..counts {
+            corrupt.push(bytes[..len].to_vec());
+        }
+        // A name length past the end of the file
+        let name = index.names()[0].as_bytes();
+        let at = bytes.windows(name.len()).position(|x| x == name).unwrap() - 1;
+        let mut huge_name = bytes[..at].to_vec();
+        huge_name.extend([0xff; 9]);
+        huge_name.push(0x01);
+        huge_name.extend(&bytes[at + 1..]);
+        corrupt.push(huge_name);
+
+        for x in corrupt {
+            assert!(matches!(open(&x), Err(Error::Format(_))));
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    pub fn test_masking() {
+        let params = Params::new(15, 7, &[4]).unwrap().with_canonical(true);
+        let unit = random_seq(300, 7);
+        let mut seq = random_seq(5_000, 9);
+        for _ in 0..50 {
+            seq.extend_from_slice(&unit);
+        }
+        let records = vec![Record {
+            id: "repeats".to_string(),
+            seq,
+        }];
+        let mut index = Index::build(params, &records);
+        let query = unit.repeat(2);
+        let hits = index.query(&query);
+        assert!(hits.iter().all(|x| x.occurrences.len() >= 49));
+        let repeats = hits.into_iter().map(|x| x.query).collect::<Vec<_>>();
+
+        let stats = index.set_cutoff(Some(Cutoff::Occurrences(10)));
+        assert_eq!(stats.max_occurrences, Some(10));
+        assert!(
+            stats.masked_syncmers > 0 && stats.masked_occurrences >= 49 * stats.masked_syncmers
+        );
+        assert_eq!(stats.total_occurrences, index.occurrences());
+        assert!(index.query(&query).is_empty());
+        assert!(!index.query(&records[0].seq[..1_000]).is_empty());
+        // Query-time override still sees the repeats
+        let unmasked = index.query_with_max_occurrences(&query, None);
+        assert_eq!(
+            unmasked.into_iter().map(|x| x.query).collect::<Vec<_>>(),
+            repeats
+        );
+
+        // The repeat syncmers are the most frequent ones
+        let fraction = stats.masked_syncmers as f64 / stats.total_syncmers as f64;
+        let by_fraction = index.set_cutoff(Some(Cutoff::Fraction(fraction)));
+        assert_eq!(by_fraction.masked_syncmers, stats.masked_syncmers);
+        assert_eq!(index.set_cutoff(None).masked_syncmers, 0);
+    }
+}
@@ -1,9 +1,9 @@
-/// Syncmers as defined by Dutta et al. 2022, https://www.biorxiv.org/content/10.1101/2022.01.10.475696v2.full
-/// Esp Fig 1b
-/// Planning to implement other methods soon
-///
-/// TODO: Add Iterator impl's
-/// TODO: Add in strobemers: https://genome.cshlp.org/content/31/11/2080.full
+//! Syncmers as defined by Dutta et al. 2022, https://www.biorxiv.org/content/10.1101/2022.01.10.475696v2.full
+//! Esp Fig 1b
+//! Planning to implement other methods soon
+//!
+//! TODO: Add Iterator impl's
+//! TODO: Add in strobemers: https://genome.cshlp.org/content/31/11/2080.full
 
 // use std::iter::{FilterMap, Enumerate};
 // use std::slice::Windows;
@@ -13,7 +13,42 @@ use pulp::Arch;
 use xxhash_rust::xxh3::xxh3_64_with_secret;
 use xxhash_rust::const_xxh3::const_custom_default_secret;
 
-const SECRET: [u8; 192] = const_custom_default_secret(42);
+pub mod align;
+pub mod chain;
+pub mod chunk;
+pub mod count;
+pub mod dist;
+mod error;
+pub mod estimate;
+pub mod evaluate;
+pub mod fasta;
+pub mod format;
+pub mod graph;
+pub mod index;
+pub mod map;
+pub mod overlap;
+mod parallel;
+mod params;
+mod random;
+pub mod sketch;
+#[cfg(feature = "sourmash")]
+pub mod sourmash;
+mod streaming;
+pub mod superkmer;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub mod theory;
+pub mod tree;
+pub mod tune;
+
+pub use error::Error;
+pub use index::{Cutoff, Index, MaskStats};
+pub use parallel::{find_syncmers_pos_par, find_syncmers_pos_par_chunked, DEFAULT_CHUNK_SIZE};
+pub use params::{Params, Strand, Syncmer, DEFAULT_SEED};
+pub use sketch::{BottomKSketch, Sketch};
+pub use streaming::SyncmerStream;
+
+const SECRET: [u8; 192] = const_custom_default_secret(DEFAULT_SEED);
 
 // TODO. For a given order, the canonical form of a k-mer x, denoted by Canonical(x), is the smaller of x and Embedded Image. For example, under the lexicographic order, Canonical(CGGT) = ACCG.
 // Canonical(x) = min(x, revcomp(x))
@@ -101,7 +136,7 @@ pub fn find_syncmers<'a, const N: usize>(
     if let Some(downsample) = downsample {
         assert!(downsample > 0.0);
         assert!(downsample <= 1.0);
-        downsample_threshold = Some((std::u64::MAX as f64 * downsample) as u64);
+        downsample_threshold = Some((u64::MAX as f64 * downsample) as u64);
     }
 
     let syncmer_positions = find_syncmers_pos(k, s, ts, seq);
@@ -164,6 +199,16 @@ pub fn find_syncmers_pos<const N: usize>(
         .collect::<Vec<_>>()
 }
 
+/// Position of the smallest smer within a kmer. Ties are broken by choosing the leftmost position
+#[inline]
+pub(crate) fn smallest_smer_pos(kmer: &[u8], s: usize) -> usize {
+    kmer.windows(s)
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.cmp(b))
+        .unwrap()
+        .0
+}
+
 /// This is SIGNIFICANTLY slower than find_syncmers function. Prefer to use that instead.
 /// t is 0-based (unlike in the paper)
 /// NOTE: "By convention, ties are broken by choosing the leftmost position"
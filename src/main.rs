@@ -0,0 +1,797 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rayon::prelude::*;
+
+use syncmers::chunk::Chunker;
+use syncmers::count::{write_histogram, DiskCounter, SyncmerCounter};
+use syncmers::dist::{
+    distance_matrix, write_distances, DistanceMatrix, MatrixFormat, DEFAULT_BLOCK_SIZE,
+};
+use syncmers::estimate::{fit_histogram, read_histogram};
+use syncmers::evaluate::{evaluate, mutate, Evaluation};
+use syncmers::fasta::FastaReader;
+use syncmers::format::is_syncmers_file;
+use syncmers::graph::{Graph, GraphParams};
+use syncmers::index::is_index_file;
+use syncmers::map::{map_read, map_read_aligned, MapParams};
+use syncmers::overlap::{find_overlaps, OverlapParams};
+use syncmers::sketch::{load_sketches, save_sketches};
+use syncmers::tree::{Tree, TreeMethod};
+use syncmers::tune::{tune, TuneParams};
+use syncmers::{Cutoff, Error, Index, Params, Sketch};
+
+#[derive(Parser)]
+#[command(version, about = "Syncmer extraction, sketching and comparison")]
+struct Cli {
+    /// Number of threads (default: all cores)
+    #[arg(short = 'j', long, global = true)]
+    threads: Option<usize>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Sketch FASTA/FASTQ files (one sketch per file) into a single sketch file
+    Sketch {
+        #[command(flatten)]
+        params: StrandParamArgs,
+
+        #[command(flatten)]
+        sketch: SketchArgs,
+
+        /// Output sketch file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// FASTA/FASTQ files
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+
+    /// All-vs-all Mash distances between FASTA files and/or sketch files
+    Dist {
+        #[command(flatten)]
+        params: StrandParamArgs,
+
+        #[command(flatten)]
+        sketch: SketchArgs,
+
+        /// Matrix format
+        #[arg(short, long, value_enum, default_value_t = Format::Phylip)]
+        format: Format,
+
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// FASTA/FASTQ files or sketch files written by `syncmers sketch`
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+
+    /// Neighbor-joining or UPGMA tree (Newick) from FASTA/sketch files or a distance matrix
+    Tree {
+        #[command(flatten)]
+        params: StrandParamArgs,
+
+        #[command(flatten)]
+        sketch: SketchArgs,
+
+        /// Tree building method
+        #[arg(short, long, value_enum, default_value_t = Method::Nj)]
+        method: Method,
+
+        /// PHYLIP or TSV matrix written by `syncmers dist`, instead of inputs
+        #[arg(long, conflicts_with = "inputs")]
+        matrix: Option<PathBuf>,
+
+        /// Format of the --matrix file
+        #[arg(short, long, value_enum, default_value_t = Format::Phylip)]
+        format: Format,
+
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// FASTA/FASTQ files or sketch files written by `syncmers sketch`
+        #[arg(required_unless_present = "matrix")]
+        inputs: Vec<PathBuf>,
+    },
+
+    /// Build a memory-mappable syncmer index of a reference for `syncmers map`
+    Index {
+        #[command(flatten)]
+        params: StrandParamArgs,
+
+        /// Output index file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Reference FASTA
+        reference: PathBuf,
+    },
+
+    /// Map reads to a reference by syncmer chaining, writing PAF (no base-level alignment)
+    Map {
+        #[command(flatten)]
+        params: StrandParamArgs,
+
+        /// Mask this fraction of the most frequent reference syncmers
+        #[arg(short = 'f', long, default_value_t = 0.0002)]
+        mask_fraction: f64,
+
+        /// Maximum number of secondary hits per primary
+        #[arg(short = 'N', long, default_value_t = 5)]
+        max_secondary: usize,
+
+        /// Base-level alignment of each hit, adding a CIGAR (needs a FASTA reference)
+        #[arg(short, long)]
+        align: bool,
+
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Reference FASTA, or index file written by `syncmers index` with the same params
+        reference: PathBuf,
+
+        /// FASTA/FASTQ reads
+        #[arg(required = true)]
+        reads: Vec<PathBuf>,
+    },
+
+    /// All-vs-all read overlaps (containments and dovetails) as PAF, for assembly
+    Overlap {
+        #[command(flatten)]
+        params: StrandParamArgs,
+
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// FASTA/FASTQ reads, overlapped together as one batch
+        #[arg(required = true)]
+        reads: Vec<PathBuf>,
+    },
+
+    /// Syncmer-space de Bruijn graph of reads, compacted into unitigs and written as GFA
+    Graph {
+        #[command(flatten)]
+        params: StrandParamArgs,
+
+        /// Consecutive syncmers per node
+        #[arg(short = 'l', long, default_value_t = 5)]
+        order: usize,
+
+        /// Drop nodes seen in fewer reads
+        #[arg(short = 'a', long, default_value_t = 2)]
+        min_abundance: usize,
+
+        /// Output GFA file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// FASTA/FASTQ reads
+        #[arg(required = true)]
+        reads: Vec<PathBuf>,
+    },
+
+    /// Split sequences into content-defined chunks starting at syncmers, writing
+    /// "name offset length hash" lines
+    Chunk {
+        #[command(flatten)]
+        params: StrandParamArgs,
+
+        /// Minimum chunk length
+        #[arg(long, default_value_t = 1024)]
+        min_size: usize,
+
+        /// Maximum chunk length
+        #[arg(long, default_value_t = 16384)]
+        max_size: usize,
+
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// FASTA/FASTQ files
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+
+    /// Rank s and target positions for a kmer length and density by expected conservation and
+    /// spacing on random sequence
+    Tune {
+        /// Kmer length
+        #[arg(short, default_value_t = 21)]
+        k: usize,
+
+        /// Target density, e.g. 0.1 for one syncmer every 10 kmers
+        #[arg(short, long)]
+        density: f64,
+
+        /// Accepted deviation from the density, relative to it
+        #[arg(long, default_value_t = 0.1)]
+        tolerance: f64,
+
+        /// Substitution rate conservation is scored at
+        #[arg(long, default_value_t = 0.05)]
+        mutation_rate: f64,
+
+        /// Largest number of target positions
+        #[arg(long, default_value_t = 4)]
+        max_targets: usize,
+
+        /// Shortest smer
+        #[arg(long, default_value_t = 5)]
+        min_s: usize,
+
+        /// Number of parameter sets to print
+        #[arg(short, long, default_value_t = 10)]
+        number: usize,
+    },
+
+    /// Density, conservation, coverage and gaps of syncmers on sequences and a copy of them
+    /// with random substitutions
+    Evaluate {
+        #[command(flatten)]
+        params: StrandParamArgs,
+
+        /// Substitution rate of the mutated copy
+        #[arg(long, default_value_t = 0.05)]
+        mutation_rate: f64,
+
+        /// Seed for the substitutions
+        #[arg(long, default_value_t = 1)]
+        mutation_seed: u64,
+
+        /// Also write "distance count" lines for the gaps between consecutive syncmers
+        #[arg(long)]
+        gaps: Option<PathBuf>,
+
+        /// FASTA/FASTQ files
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+
+    /// Count canonical syncmers, writing a jellyfish-style histogram and optionally a dump
+    Count {
+        #[command(flatten)]
+        params: ParamArgs,
+
+        /// Histogram output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Counts above this are added to the last histogram bin
+        #[arg(long, default_value_t = 10_000)]
+        max_count: usize,
+
+        /// Also write "kmer count" lines to this file
+        #[arg(long)]
+        dump: Option<PathBuf>,
+
+        /// Only dump syncmers counted at least this many times
+        #[arg(short = 'L', long, default_value_t = 1)]
+        min_count: u32,
+
+        /// Memory budget in GB: spill syncmers to bucket files instead of counting in memory
+        #[arg(short, long)]
+        memory: Option<f64>,
+
+        /// Directory for bucket files (default: system temporary directory)
+        #[arg(long, requires = "memory")]
+        tmp_dir: Option<PathBuf>,
+
+        /// FASTA/FASTQ files
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+
+    /// Estimate genome size, coverage and heterozygosity from reads or a `syncmers count`
+    /// histogram (which must have been counted with the same -k, -s and -t)
+    Estimate {
+        #[command(flatten)]
+        params: ParamArgs,
+
+        /// Histogram written by `syncmers count`, instead of reads
+        #[arg(long, conflicts_with = "inputs")]
+        histogram: Option<PathBuf>,
+
+        /// Counts above this are pooled into the last histogram bin
+        #[arg(long, default_value_t = 10_000)]
+        max_count: usize,
+
+        /// FASTA/FASTQ reads
+        #[arg(required_unless_present = "histogram")]
+        inputs: Vec<PathBuf>,
+    },
+}
+
+/// Reads mapped in parallel per batch, keeping output in input order
+const MAP_BATCH_SIZE: usize = 4096;
+
+#[derive(Args)]
+struct ParamArgs {
+    /// Kmer length
+    #[arg(short, default_value_t = 21)]
+    k: usize,
+
+    /// Smer length
+    #[arg(short, default_value_t = 11)]
+    s: usize,
+
+    /// Target positions (0-based), may be given more than once
+    #[arg(short, default_values_t = vec![0])]
+    t: Vec<usize>,
+
+    /// Hash seed
+    #[arg(long, default_value_t = syncmers::DEFAULT_SEED)]
+    seed: u64,
+}
+
+impl ParamArgs {
+    /// Canonical params, for the subcommands that always count both strands as one
+    fn params(&self) -> Result<Params, Error> {
+        Ok(Params::new(self.k, self.s, &self.t)?
+            .with_seed(self.seed)
+            .with_canonical(true))
+    }
+}
+
+/// Params for the subcommands that can also work on the forward strand only
+#[derive(Args)]
+struct StrandParamArgs {
+    #[command(flatten)]
+    params: ParamArgs,
+
+    /// Only use the forward strand instead of canonical kmers
+    #[arg(long)]
+    no_canonical: bool,
+}
+
+impl StrandParamArgs {
+    fn params(&self) -> Result<Params, Error> {
+        Ok(self.params.params()?.with_canonical(!self.no_canonical))
+    }
+}
+
+#[derive(Args)]
+struct SketchArgs {
+    /// Keep hashes below u64::MAX / scaled
+    #[arg(long, default_value_t = 1000)]
+    scaled: u64,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Phylip,
+    Tsv,
+}
+
+impl From<Format> for MatrixFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Phylip => MatrixFormat::Phylip,
+            Format::Tsv => MatrixFormat::Tsv,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Method {
+    Nj,
+    Upgma,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .expect("Unable to configure thread pool");
+    }
+
+    if let Err(e) = run(cli.command) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(command: Command) -> Result<(), Error> {
+    match command {
+        Command::Sketch {
+            params,
+            sketch,
+            output,
+            inputs,
+        } => {
+            let sketches = sketch_fastas(&params, &sketch, &inputs)?;
+            save_sketches(output, &sketches)
+        }
+        Command::Dist {
+            params,
+            sketch,
+            format,
+            output,
+            inputs,
+        } => {
+            let (labels, sketches): (Vec<_>, Vec<_>) =
+                load_inputs(&params, &sketch, &inputs)?.into_iter().unzip();
+            let out = open_output(output.as_deref())?;
+            write_distances(&labels, &sketches, format.into(), DEFAULT_BLOCK_SIZE, out)
+        }
+        Command::Tree {
+            params,
+            sketch,
+            method,
+            matrix,
+            format,
+            output,
+            inputs,
+        } => {
+            let matrix = match matrix {
+                Some(path) => {
+                    DistanceMatrix::read(BufReader::new(File::open(path)?), format.into())?
+                }
+                None => {
+                    let (labels, sketches): (Vec<_>, Vec<_>) =
+                        load_inputs(&params, &sketch, &inputs)?.into_iter().unzip();
+                    distance_matrix(labels, &sketches)?
+                }
+            };
+            let method = match method {
+                Method::Nj => TreeMethod::NeighborJoining,
+                Method::Upgma => TreeMethod::Upgma,
+            };
+            let mut out = open_output(output.as_deref())?;
+            writeln!(out, "{}", Tree::build(&matrix, method).to_newick())?;
+            out.flush()?;
+            Ok(())
+        }
+        Command::Index {
+            params,
+            output,
+            reference,
+        } => Index::from_fasta(params.params()?, reference)?.save(output),
+        Command::Map {
+            params,
+            mask_fraction,
+            max_secondary,
+            align,
+            output,
+            reference,
+            reads,
+        } => {
+            // Sequences are only kept for alignment; index files don't store them
+            let mut references = Vec::new();
+            let index = if is_index_file(&reference)? {
+                if align {
+                    return Err(Error::InvalidParams(
+                        "--align needs a FASTA reference, index files don't store sequence"
+                            .to_string(),
+                    ));
+                }
+                Index::open_with_params(&reference, &params.params()?)?
+            } else {
+                references = FastaReader::from_path(&reference)?.collect::<Result<Vec<_>, _>>()?;
+                Index::build(params.params()?, &references)
+            };
+            let index = index.with_cutoff(Cutoff::Fraction(mask_fraction));
+            let map_params = MapParams {
+                max_secondary,
+                ..Default::default()
+            };
+
+            let mut out = open_output(output.as_deref())?;
+            for path in reads {
+                let mut records = FastaReader::from_path(path)?.peekable();
+                while records.peek().is_some() {
+                    let batch = records
+                        .by_ref()
+                        .take(MAP_BATCH_SIZE)
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let mappings = batch
+                        .par_iter()
+                        .map(|x| {
+                            if align {
+                                map_read_aligned(&index, &references, &x.id, &x.seq, &map_params)
+                            } else {
+                                map_read(&index, &x.id, &x.seq, &map_params)
+                            }
+                        })
+                        .collect::<Vec<_>>();
+                    for mapping in mappings.iter().flatten() {
+                        writeln!(out, "{}", mapping.to_paf())?;
+                    }
+                }
+            }
+            out.flush()?;
+            Ok(())
+        }
+        Command::Overlap {
+            params,
+            output,
+            reads,
+        } => {
+            let mut records = Vec::new();
+            for path in reads {
+                for record in FastaReader::from_path(path)? {
+                    records.push(record?);
+                }
+            }
+            let overlaps = find_overlaps(&params.params()?, &records, &OverlapParams::default());
+            let mut out = open_output(output.as_deref())?;
+            for overlap in overlaps {
+                writeln!(out, "{}", overlap.to_paf())?;
+            }
+            out.flush()?;
+            Ok(())
+        }
+        Command::Graph {
+            params,
+            order,
+            min_abundance,
+            output,
+            reads,
+        } => {
+            let mut records = Vec::new();
+            for path in reads {
+                for record in FastaReader::from_path(path)? {
+                    records.push(record?);
+                }
+            }
+            let graph_params = GraphParams {
+                order,
+                min_abundance,
+            };
+            let graph = Graph::build(&params.params()?, &records, &graph_params);
+            let mut out = open_output(output.as_deref())?;
+            graph.write_gfa(&mut out)?;
+            out.flush()?;
+            Ok(())
+        }
+        Command::Chunk {
+            params,
+            min_size,
+            max_size,
+            output,
+            inputs,
+        } => {
+            let chunker = Chunker::new(params.params()?, min_size, max_size)?;
+            let mut out = open_output(output.as_deref())?;
+            for path in inputs {
+                for record in FastaReader::from_path(path)? {
+                    let record = record?;
+                    for chunk in chunker.chunks(&record.seq) {
+                        writeln!(
+                            out,
+                            "{}\t{}\t{}\t{:016x}",
+                            record.id, chunk.offset, chunk.len, chunk.hash
+                        )?;
+                    }
+                }
+            }
+            out.flush()?;
+            Ok(())
+        }
+        Command::Tune {
+            k,
+            density,
+            tolerance,
+            mutation_rate,
+            max_targets,
+            min_s,
+            number,
+        } => {
+            let tune_params = TuneParams {
+                tolerance,
+                mutation_rate,
+                max_targets,
+                min_s,
+            };
+            let candidates = tune(k, density, &tune_params)?;
+            if candidates.is_empty() {
+                return Err(Error::InvalidParams(format!(
+                    "No s and target positions give a density within {} of {} for k = {}",
+                    tolerance, density, k
+                )));
+            }
+            println!("#k\ts\tts\tdensity\tconserved_coverage\tspacing_sd\tmax_spacing");
+            for x in candidates.iter().take(number) {
+                let ts = x
+                    .params
+                    .ts()
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect::<Vec<_>>();
+                println!(
+                    "{}\t{}\t{}\t{:.4}\t{:.4}\t{:.2}\t{}",
+                    k,
+                    x.params.s(),
+                    ts.join(","),
+                    x.density,
+                    x.conserved_coverage,
+                    x.spacing_sd,
+                    x.max_spacing
+                        .map_or("unbounded".to_string(), |x| x.to_string())
+                );
+            }
+            Ok(())
+        }
+        Command::Evaluate {
+            params,
+            mutation_rate,
+            mutation_seed,
+            gaps,
+            inputs,
+        } => {
+            let params = params.params()?;
+            let mut total = Evaluation::default();
+            let mut seed = mutation_seed;
+            for path in inputs {
+                for record in FastaReader::from_path(path)? {
+                    let record = record?;
+                    let mutated = mutate(&record.seq, mutation_rate, seed);
+                    total.merge(&evaluate(&params, &record.seq, &mutated)?);
+                    seed = seed.wrapping_add(1);
+                }
+            }
+            println!("bases\t{}", total.bases);
+            println!("syncmers\t{}", total.syncmers);
+            println!("density\t{:.6}", total.density());
+            println!("conservation\t{:.6}", total.conservation());
+            println!("coverage\t{:.6}", total.coverage());
+            println!("conserved_coverage\t{:.6}", total.conserved_coverage());
+            println!("mean_gap\t{:.2}", total.mean_gap());
+            println!("largest_gap\t{}", total.largest_gap());
+            if let Some(path) = gaps {
+                let mut out = BufWriter::new(File::create(path)?);
+                for (distance, &count) in total.gaps.iter().enumerate() {
+                    if count > 0 {
+                        writeln!(out, "{}\t{}", distance, count)?;
+                    }
+                }
+                out.flush()?;
+            }
+            Ok(())
+        }
+        Command::Count {
+            params,
+            output,
+            max_count,
+            dump,
+            min_count,
+            memory,
+            tmp_dir,
+            inputs,
+        } => {
+            let counts = match memory {
+                None => {
+                    let mut counter = SyncmerCounter::new(params.params()?)?;
+                    for path in inputs {
+                        counter.add_fasta(path)?;
+                    }
+                    counter.finish()
+                }
+                Some(gb) => {
+                    let tmp_dir = tmp_dir.unwrap_or_else(std::env::temp_dir);
+                    let memory = (gb * 1e9) as usize;
+                    let mut counter = DiskCounter::new(params.params()?, tmp_dir, memory)?;
+                    for path in inputs {
+                        counter.add_fasta(path)?;
+                    }
+                    // One bucket in memory at a time
+                    let histogram = match &dump {
+                        Some(path) => counter.finish_dump(
+                            min_count,
+                            max_count,
+                            BufWriter::new(File::create(path)?),
+                        )?,
+                        None => counter.finish_histogram(max_count)?,
+                    };
+                    return write_histogram(&histogram, open_output(output.as_deref())?);
+                }
+            };
+            if let Some(path) = dump {
+                counts.write_dump(min_count, BufWriter::new(File::create(path)?))?;
+            }
+            counts.write_histogram(max_count, open_output(output.as_deref())?)
+        }
+        Command::Estimate {
+            params,
+            histogram,
+            max_count,
+            inputs,
+        } => {
+            let params = params.params()?;
+            let histogram = match histogram {
+                Some(path) => read_histogram(BufReader::new(File::open(path)?))?,
+                None => {
+                    let mut counter = SyncmerCounter::new(params.clone())?;
+                    for path in inputs {
+                        counter.add_fasta(path)?;
+                    }
+                    counter.finish().histogram(max_count)
+                }
+            };
+            let fit = fit_histogram(&histogram, &params).ok_or_else(|| {
+                Error::Format("No coverage peak found in the histogram".to_string())
+            })?;
+            println!("genome_size\t{:.0}", fit.genome_size);
+            println!("heterozygosity\t{:.6}", fit.heterozygosity);
+            println!("coverage\t{:.2}", fit.coverage);
+            println!("genome_syncmers\t{:.0}", fit.genome_syncmers);
+            println!("density\t{:.6}", fit.density);
+            println!("error_cutoff\t{}", fit.error_cutoff);
+            println!("model_fit\t{:.4}", fit.model_fit);
+            Ok(())
+        }
+    }
+}
+
+fn open_output(path: Option<&Path>) -> Result<Box<dyn Write>, Error> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    })
+}
+
+fn sketch_fastas(
+    params: &StrandParamArgs,
+    sketch: &SketchArgs,
+    inputs: &[PathBuf],
+) -> Result<Vec<(String, Sketch)>, Error> {
+    let sketch_params = params.params()?;
+    inputs
+        .par_iter()
+        .map(|path| {
+            let sketch = Sketch::from_fasta(sketch_params.clone(), sketch.scaled, path)?;
+            Ok((path.display().to_string(), sketch))
+        })
+        .collect()
+}
+
+/// Sketch FASTA inputs and load sketch files, keeping the order given on the command line
+fn load_inputs(
+    params: &StrandParamArgs,
+    sketch: &SketchArgs,
+    inputs: &[PathBuf],
+) -> Result<Vec<(String, Sketch)>, Error> {
+    let sketch_params = params.params()?;
+    let sketches = inputs
+        .par_iter()
+        .map(|path| {
+            if is_syncmers_file(path)? {
+                load_sketches(path)
+            } else {
+                let sketch = Sketch::from_fasta(sketch_params.clone(), sketch.scaled, path)?;
+                Ok(vec![(path.display().to_string(), sketch)])
+            }
+        })
+        .collect::<Result<Vec<_>, Error>>()?
+        .concat();
+
+    // Sketch files carry their own parameters, which must all agree
+    if let Some((_, first)) = sketches.first() {
+        for (name, sketch) in sketches.iter() {
+            if first.params() != sketch.params() || first.scaled() != sketch.scaled() {
+                return Err(Error::IncompatibleParams(format!(
+                    "{} was sketched with {:?} scaled {}, expected {:?} scaled {}",
+                    name,
+                    sketch.params(),
+                    sketch.scaled(),
+                    first.params(),
+                    first.scaled()
+                )));
+            }
+        }
+    }
+    Ok(sketches)
+}
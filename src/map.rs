@@ -0,0 +1,350 @@
+//! Approximate read-to-reference mapping: index lookup, chaining and PAF output
+//!
+//! Reads are placed by their best syncmer chains, without base-level alignment, which is
+//! enough for contamination and placement checks. Primary and secondary hits and mapping
+//! quality follow minimap2: a chain overlapping a better one on the read by more than
+//! `mask_level` of the shorter is secondary to it, and the best such chain lowers the
+//! primary's MAPQ.
+//!
+//! [`map_read_aligned`] adds an optional base-level step, aligning each hit with
+//! [`crate::align`] for a CIGAR and exact identity.
+
+use crate::align::{align_chain, Alignment};
+use crate::chain::{chain, Anchor, Chain, ChainParams};
+use crate::fasta::Record;
+use crate::{Index, Strand};
+
+/// Mapping parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapParams {
+    pub chain: ChainParams,
+    /// Maximum number of secondary hits reported per primary
+    pub max_secondary: usize,
+    /// Secondary hits scoring less than this fraction of their primary are not reported
+    pub secondary_ratio: f64,
+    /// Fraction of the shorter of two overlapping hits that makes the worse one secondary
+    pub mask_level: f64,
+    /// Extra diagonals around the gaps between anchors in base-level alignment
+    pub band: usize,
+}
+
+impl Default for MapParams {
+    fn default() -> Self {
+        MapParams {
+            chain: ChainParams::default(),
+            max_secondary: 5,
+            secondary_ratio: 0.8,
+            mask_level: 0.5,
+            band: 64,
+        }
+    }
+}
+
+/// A read placed on a reference record. Coordinates are half-open on the forward strands
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping {
+    pub query_name: String,
+    pub query_len: usize,
+    pub query_start: usize,
+    pub query_end: usize,
+    pub strand: Strand,
+    pub target_name: String,
+    pub target_len: usize,
+    pub target_start: usize,
+    pub target_end: usize,
+    /// Read bases covered by anchors, a lower bound on matching bases
+    pub matches: usize,
+    /// Longer of the query and target spans
+    pub block_len: usize,
+    pub mapq: u8,
+    pub primary: bool,
+    pub anchors: usize,
+    pub score: f64,
+    /// Base-level alignment, from [`map_read_aligned`]
+    pub alignment: Option<Alignment>,
+}
+
+impl Mapping {
+    /// PAF line (without newline), with tp (P/S), cm (anchors) and s1 (chain score) tags, and
+    /// cg (CIGAR) when aligned
+    pub fn to_paf(&self) -> String {
+        let mut paf = format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\ttp:A:{}\tcm:i:{}\ts1:i:{}",
+            self.query_name,
+            self.query_len,
+            self.query_start,
+            self.query_end,
+            match self.strand {
+                Strand::Forward => '+',
+                Strand::Reverse => '-',
+            },
+            self.target_name,
+            self.target_len,
+            self.target_start,
+            self.target_end,
+            self.matches,
+            self.block_len,
+            self.mapq,
+            if self.primary { 'P' } else { 'S' },
+            self.anchors,
+            self.score.round() as i64,
+        );
+        if let Some(alignment) = &self.alignment {
+            paf.push_str("\tcg:Z:");
+            paf.push_str(&alignment.cigar_string());
+        }
+        paf
+    }
+}
+
+/// minimap2's MAPQ from the primary score f1, the best secondary score f2 and the number of
+/// anchors
+fn mapq(f1: f64, f2: f64, anchors: usize) -> u8 {
+    let q = 40.0 * (1.0 - f2 / f1) * (anchors as f64 / 10.0).min(1.0) * f1.ln();
+    q.clamp(0.0, 60.0) as u8
+}
+
+/// Read bases covered by the anchors of a chain
+pub(crate) fn covered(chain: &Chain) -> usize {
+    let mut intervals = chain
+        .anchors
+        .iter()
+        .map(|x| (x.query_pos, x.query_pos + x.k))
+        .collect::<Vec<_>>();
+    intervals.sort_unstable();
+    let (mut total, mut covered_end) = (0, 0);
+    for (start, end) in intervals {
+        total += end.saturating_sub(start.max(covered_end));
+        covered_end = covered_end.max(end);
+    }
+    total
+}
+
+fn overlap(a: &Chain, b: &Chain) -> usize {
+    a.query_end
+        .min(b.query_end)
+        .saturating_sub(a.query_start.max(b.query_start))
+}
+
+/// Anchors between seq and the index's records, as (record, anchor) sorted by record
+pub(crate) fn record_anchors(index: &Index, seq: &[u8]) -> Vec<(u32, Anchor)> {
+    let mut anchors = Vec::new();
+    for hit in index.query(seq) {
+        for occurrence in hit.occurrences.iter() {
+            let strand = if occurrence.strand == hit.query.strand {
+                Strand::Forward
+            } else {
+                Strand::Reverse
+            };
+            let anchor = Anchor {
+                query_pos: hit.query.pos,
+                ref_pos: occurrence.pos as usize,
+                strand,
+                k: index.params().k(),
+            };
+            anchors.push((occurrence.record, anchor));
+        }
+    }
+    anchors.sort_unstable_by_key(|x| x.0);
+    anchors
+}
+
+/// Map a read, returning its primary and secondary hits by decreasing score (empty if it
+/// doesn't map)
+pub fn map_read(index: &Index, name: &str, seq: &[u8], params: &MapParams) -> Vec<Mapping> {
+    map(index, None, name, seq, params)
+}
+
+/// [`map_read`] followed by base-level alignment of every hit. references are the records
+/// the index was built from, in the same order. matches and block_len then come from the
+/// alignment
+pub fn map_read_aligned(
+    index: &Index,
+    references: &[Record],
+    name: &str,
+    seq: &[u8],
+    params: &MapParams,
+) -> Vec<Mapping> {
+    assert_eq!(index.names().len(), references.len());
+    map(index, Some(references), name, seq, params)
+}
+
+fn map(
+    index: &Index,
+    references: Option<&[Record]>,
+    name: &str,
+    seq: &[u8],
+    params: &MapParams,
+) -> Vec<Mapping> {
+    let anchors = record_anchors(index, seq);
+
+    let mut chains = Vec::new();
+    for record in anchors.chunk_by(|a, b| a.0 == b.0) {
+        let anchors = record.iter().map(|x| x.1).collect::<Vec<_>>();
+        for c in chain(&anchors, &params.chain) {
+            chains.push((record[0].0, c));
+        }
+    }
+    chains.sort_by(|a, b| b.1.score.total_cmp(&a.1.score));
+
+    // (primary chain, best secondary score, reported secondaries)
+    let mut primaries: Vec<(usize, f64, Vec<usize>)> = Vec::new();
+    for (i, (_, c)) in chains.iter().enumerate() {
+        let parent = primaries.iter_mut().find(|(p, _, _)| {
+            let p = &chains[*p].1;
+            let shorter = (c.query_end - c.query_start).min(p.query_end - p.query_start);
+            overlap(c, p) as f64 > params.mask_level * shorter as f64
+        });
+        match parent {
+            None => primaries.push((i, 0.0, Vec::new())),
+            Some((p, sub, secondaries)) => {
+                *sub = sub.max(c.score);
+                if c.score >= params.secondary_ratio * chains[*p].1.score
+                    && secondaries.len() < params.max_secondary
+                {
+                    secondaries.push(i);
+                }
+            }
+        }
+    }
+
+    let mapping = |i: usize, primary: bool, mapq: u8| {
+        let (record, c) = &chains[i];
+        let alignment =
+            references.map(|x| align_chain(c, seq, &x[*record as usize].seq, params.band));
+        let (matches, block_len) = match &alignment {
+            Some(x) => (x.matches(), x.len()),
+            None => (
+                covered(c),
+                (c.query_end - c.query_start).max(c.ref_end - c.ref_start),
+            ),
+        };
+        Mapping {
+            query_name: name.to_string(),
+            query_len: seq.len(),
+            query_start: c.query_start,
+            query_end: c.query_end,
+            strand: c.strand,
+            target_name: index.names()[*record as usize].clone(),
+            target_len: index.lengths()[*record as usize],
+            target_start: c.ref_start,
+            target_end: c.ref_end,
+            matches,
+            block_len,
+            mapq,
+            primary,
+            anchors: c.anchors.len(),
+            score: c.score,
+            alignment,
+        }
+    };
+
+    let mut mappings = Vec::new();
+    for (p, sub, secondaries) in primaries {
+        let c = &chains[p].1;
+        mappings.push(mapping(p, true, mapq(c.score, sub, c.anchors.len())));
+        mappings.extend(secondaries.into_iter().map(|i| mapping(i, false, 0)));
+    }
+    mappings.sort_by(|a, b| b.score.total_cmp(&a.score));
+    mappings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fasta::Record;
+    use crate::test_util::random_seq;
+    use crate::{revcomp, Params};
+
+    fn references() -> Vec<Record> {
+        // b carries a second copy of a's first 2kb
+        let a = random_seq(20_000, 13);
+        let mut b = random_seq(20_000, 17);
+        b.splice(10_000..12_000, a[..2_000].iter().copied());
+        vec![
+            Record {
+                id: "a".to_string(),
+                seq: a,
+            },
+            Record {
+                id: "b".to_string(),
+                seq: b,
+            },
+        ]
+    }
+
+    fn index() -> Index {
+        let params = Params::new(15, 7, &[4]).unwrap().with_canonical(true);
+        Index::build(params, &references())
+    }
+
+    #[test]
+    pub fn test_map_unique_and_repeat() {
+        let index = index();
+        let b = random_seq(20_000, 17);
+
+        let mut read = b[3_000..6_000].to_vec();
+        for i in (0..read.len()).step_by(50) {
+            read[i] = if read[i] == b'A' { b'C' } else { b'A' };
+        }
+        revcomp(&mut read);
+        let mappings = map_read(&index, "read", &read, &MapParams::default());
+        assert_eq!(mappings.len(), 1);
+        let m = &mappings[0];
+        assert!(m.primary && m.strand == Strand::Reverse && m.target_name == "b");
+        assert!(m.target_start < 3_050 && m.target_end > 5_950);
+        assert!(m.mapq >= 30);
+        assert!(m.to_paf().starts_with("read\t3000\t"));
+        assert!(m.to_paf().contains("\t-\tb\t20000\t"));
+
+        // Reads from the repeat map equally well to both copies
+        let a = random_seq(20_000, 13);
+        let mappings = map_read(&index, "repeat", &a[500..1_500], &MapParams::default());
+        assert_eq!(mappings.len(), 2);
+        assert!(mappings[0].primary && !mappings[1].primary);
+        assert_eq!(mappings[0].mapq, 0);
+
+        assert!(map_read(
+            &index,
+            "none",
+            &random_seq(1_000, 19),
+            &MapParams::default()
+        )
+        .is_empty());
+    }
+
+    #[test]
+    pub fn test_map_aligned() {
+        let references = references();
+        let index = index();
+        let mut read = references[0].seq[8_000..11_000].to_vec();
+        for i in (0..read.len()).step_by(100) {
+            read[i] = if read[i] == b'A' { b'C' } else { b'A' };
+        }
+        read.drain(1_500..1_503);
+        revcomp(&mut read);
+
+        let mappings = map_read_aligned(&index, &references, "read", &read, &MapParams::default());
+        let m = &mappings[0];
+        let alignment = m.alignment.as_ref().unwrap();
+        assert_eq!(
+            (m.target_start, m.target_end),
+            (alignment.ref_start, alignment.ref_end)
+        );
+        assert_eq!(
+            (m.query_start, m.query_end),
+            (alignment.query_start, alignment.query_end)
+        );
+        assert!(alignment.identity() > 0.98 && alignment.identity() < 1.0);
+        assert_eq!(m.matches, alignment.matches());
+        assert!(m.to_paf().contains("\tcg:Z:"));
+    }
+
+    #[test]
+    pub fn test_mapq() {
+        assert_eq!(mapq(100.0, 100.0, 20), 0);
+        assert_eq!(mapq(1000.0, 0.0, 20), 60);
+        assert!(mapq(100.0, 90.0, 20) < mapq(100.0, 50.0, 20));
+        assert!(mapq(100.0, 50.0, 3) < mapq(100.0, 50.0, 20));
+    }
+}
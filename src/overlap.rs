@@ -0,0 +1,268 @@
+//! All-vs-all read overlaps for assembly preprocessing, like minimap2's `ava-ont` mode
+//!
+//! A batch of reads is indexed by canonical syncmers, and each read is chained against the
+//! reads after it in the batch, so self-hits and the mirrored (B, A) pair of every (A, B)
+//! overlap are never produced. The best chain of each pair is classified the way miniasm does:
+//! when the unaligned overhang is small the overlap is a containment or a dovetail, otherwise
+//! it is an internal match (usually a repeat) and is dropped.
+
+use rayon::prelude::*;
+
+use crate::chain::{chain, ChainParams};
+use crate::fasta::Record;
+use crate::map::{covered, record_anchors};
+use crate::{Cutoff, Index, Params, Strand};
+
+/// Overlap parameters. The defaults follow minimap2's `ava-ont` preset and miniasm
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlapParams {
+    pub chain: ChainParams,
+    /// Masks the most repetitive syncmers of the batch
+    pub cutoff: Option<Cutoff>,
+    /// Maximum overhang to still call a containment or dovetail
+    pub max_overhang: usize,
+    /// Maximum overhang as a fraction of the overlap length
+    pub max_overhang_ratio: f64,
+}
+
+impl Default for OverlapParams {
+    fn default() -> Self {
+        OverlapParams {
+            chain: ChainParams {
+                max_gap: 10_000,
+                bandwidth: 2_000,
+                min_score: 100.0,
+                ..Default::default()
+            },
+            cutoff: Some(Cutoff::Fraction(0.0002)),
+            max_overhang: 1_000,
+            max_overhang_ratio: 0.8,
+        }
+    }
+}
+
+/// How two reads overlap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlapKind {
+    /// The query lies within the target
+    QueryContained,
+    /// The target lies within the query
+    TargetContained,
+    /// The end of one read overlaps the start of the other
+    Dovetail,
+}
+
+/// An overlap between two reads. Coordinates are half-open on the forward strands
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlap {
+    pub query_name: String,
+    pub query_len: usize,
+    pub query_start: usize,
+    pub query_end: usize,
+    pub strand: Strand,
+    pub target_name: String,
+    pub target_len: usize,
+    pub target_start: usize,
+    pub target_end: usize,
+    /// Query bases covered by anchors
+    pub matches: usize,
+    /// Longer of the query and target spans
+    pub block_len: usize,
+    pub kind: OverlapKind,
+    pub anchors: usize,
+    pub score: f64,
+}
+
+impl Overlap {
+    /// PAF line (without newline), with cm (anchors), s1 (chain score) and ov (kind) tags
+    pub fn to_paf(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t255\tcm:i:{}\ts1:i:{}\tov:Z:{}",
+            self.query_name,
+            self.query_len,
+            self.query_start,
+            self.query_end,
+            match self.strand {
+                Strand::Forward => '+',
+                Strand::Reverse => '-',
+            },
+            self.target_name,
+            self.target_len,
+            self.target_start,
+            self.target_end,
+            self.matches,
+            self.block_len,
+            self.anchors,
+            self.score.round() as i64,
+            match self.kind {
+                OverlapKind::QueryContained => "query_contained",
+                OverlapKind::TargetContained => "target_contained",
+                OverlapKind::Dovetail => "dovetail",
+            }
+        )
+    }
+}
+
+/// miniasm's classification of a match between a query [qs, qe) of length ql and a target
+/// [ts, te) of length tl, with target coordinates already on the query's strand. None for
+/// internal matches
+fn classify(
+    (qs, qe, ql): (usize, usize, usize),
+    (ts, te, tl): (usize, usize, usize),
+    params: &OverlapParams,
+) -> Option<OverlapKind> {
+    let overhang = qs.min(ts) + (ql - qe).min(tl - te);
+    let span = (qe - qs).max(te - ts);
+    if overhang as f64 > (params.max_overhang as f64).min(span as f64 * params.max_overhang_ratio) {
+        None
+    } else if qs <= ts && ql - qe <= tl - te {
+        Some(OverlapKind::QueryContained)
+    } else if qs >= ts && ql - qe >= tl - te {
+        Some(OverlapKind::TargetContained)
+    } else {
+        Some(OverlapKind::Dovetail)
+    }
+}
+
+/// Overlaps between all pairs of reads, at most one per pair
+pub fn find_overlaps(
+    params: &Params,
+    reads: &[Record],
+    overlap_params: &OverlapParams,
+) -> Vec<Overlap> {
+    let mut index = Index::build(params.clone().with_canonical(true), reads);
+    index.set_cutoff(overlap_params.cutoff);
+
+    reads
+        .par_iter()
+        .enumerate()
+        .flat_map_iter(|(i, read)| {
+            let anchors = record_anchors(&index, &read.seq);
+            let mut overlaps = Vec::new();
+            for target in anchors.chunk_by(|a, b| a.0 == b.0) {
+                let j = target[0].0 as usize;
+                // Each pair once, from its earlier read
+                if j <= i {
+                    continue;
+                }
+                let target_anchors = target.iter().map(|x| x.1).collect::<Vec<_>>();
+                let Some(best) = chain(&target_anchors, &overlap_params.chain)
+                    .into_iter()
+                    .next()
+                else {
+                    continue;
+                };
+
+                let (ql, tl) = (read.seq.len(), reads[j].seq.len());
+                let (ts, te) = match best.strand {
+                    Strand::Forward => (best.ref_start, best.ref_end),
+                    Strand::Reverse => (tl - best.ref_end, tl - best.ref_start),
+                };
+                let Some(kind) = classify(
+                    (best.query_start, best.query_end, ql),
+                    (ts, te, tl),
+                    overlap_params,
+                ) else {
+                    continue;
+                };
+
+                overlaps.push(Overlap {
+                    query_name: read.id.clone(),
+                    query_len: ql,
+                    query_start: best.query_start,
+                    query_end: best.query_end,
+                    strand: best.strand,
+                    target_name: reads[j].id.clone(),
+                    target_len: tl,
+                    target_start: best.ref_start,
+                    target_end: best.ref_end,
+                    matches: covered(&best),
+                    block_len: (best.query_end - best.query_start)
+                        .max(best.ref_end - best.ref_start),
+                    kind,
+                    anchors: best.anchors.len(),
+                    score: best.score,
+                });
+            }
+            overlaps
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::revcomp;
+    use crate::test_util::random_seq;
+
+    #[test]
+    pub fn test_overlaps() {
+        let genome = random_seq(30_000, 23);
+        let read = |id: &str, start: usize, end: usize, reverse: bool| {
+            let mut seq = genome[start..end].to_vec();
+            if reverse {
+                revcomp(&mut seq);
+            }
+            Record {
+                id: id.to_string(),
+                seq,
+            }
+        };
+        let reads = vec![
+            read("a", 0, 8_000, false),
+            read("b", 5_000, 13_000, true),
+            read("c", 6_000, 7_000, false),
+            read("d", 20_000, 28_000, false),
+        ];
+        let params = Params::new(15, 7, &[4]).unwrap();
+        let overlaps = find_overlaps(&params, &reads, &OverlapParams::default());
+
+        let find = |q: &str, t: &str| {
+            overlaps
+                .iter()
+                .find(|x| x.query_name == q && x.target_name == t)
+        };
+        // a-b, a-c and b-c, once each, no self-hits and nothing with d
+        assert_eq!(overlaps.len(), 3);
+        assert!(overlaps.iter().all(|x| x.query_name < x.target_name));
+
+        let ab = find("a", "b").unwrap();
+        assert_eq!(
+            (ab.kind, ab.strand),
+            (OverlapKind::Dovetail, Strand::Reverse)
+        );
+        assert!(ab.query_start < 5_050 && ab.query_end > 7_950);
+        // b is reverse complemented, so the overlap is at its end
+        assert!(ab.target_start > 4_950 && ab.target_start < 5_050 && ab.target_end > 7_950);
+
+        let ac = find("a", "c").unwrap();
+        assert_eq!(
+            (ac.kind, ac.strand),
+            (OverlapKind::TargetContained, Strand::Forward)
+        );
+        let bc = find("b", "c").unwrap();
+        assert_eq!(
+            (bc.kind, bc.strand),
+            (OverlapKind::TargetContained, Strand::Reverse)
+        );
+        assert!(ab.to_paf().ends_with("ov:Z:dovetail"));
+    }
+
+    #[test]
+    pub fn test_classify() {
+        let params = OverlapParams::default();
+        assert_eq!(
+            classify((0, 1_000, 1_000), (500, 1_500, 5_000), &params),
+            Some(OverlapKind::QueryContained)
+        );
+        assert_eq!(
+            classify((4_000, 5_000, 5_000), (0, 1_000, 3_000), &params),
+            Some(OverlapKind::Dovetail)
+        );
+        // A repeat in the middle of both reads
+        assert_eq!(
+            classify((2_000, 3_000, 5_000), (2_000, 3_000, 5_000), &params),
+            None
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Parallel syncmer extraction for long sequences (chromosome-scale &[u8])
+//!
+//! The sequence is split into chunks of kmer start positions. Each chunk is given the
+//! k-1 bases that follow it, so every kmer is seen by exactly one chunk and the merged
+//! result is identical to [`crate::find_syncmers_pos`].
+
+use rayon::prelude::*;
+
+use crate::find_syncmers_pos;
+
+/// Default number of kmer start positions per chunk (4 MiB)
+pub const DEFAULT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Find positions of syncmers using all available threads
+///
+/// Same arguments and output as [`crate::find_syncmers_pos`], but the sequence is split into
+/// chunks of [`DEFAULT_CHUNK_SIZE`] kmers that are processed in parallel.
+///
+/// ```rust
+/// # use syncmers::{find_syncmers_pos, find_syncmers_pos_par};
+/// let sequence = b"CCAGTGTTTACGG";
+/// assert_eq!(find_syncmers_pos_par(5, 2, &[2], sequence), find_syncmers_pos(5, 2, &[2], sequence));
+/// ```
+pub fn find_syncmers_pos_par<const N: usize>(
+    k: usize,
+    s: usize,
+    ts: &[usize; N],
+    seq: &[u8],
+) -> Vec<usize> {
+    find_syncmers_pos_par_chunked(k, s, ts, DEFAULT_CHUNK_SIZE, seq)
+}
+
+/// Find positions of syncmers in parallel, with an explicit chunk size
+///
+/// # Arguments
+/// k: kmer length
+/// s: smer length
+/// ts: Target positions, see [`crate::find_syncmers_pos`]
+/// chunk_size: Number of kmer start positions handled by each task. Neighbouring chunks
+///     overlap by k-1 bases.
+///
+/// # Returns
+/// Vec<usize> of positions of syncmers, sorted, identical to the serial result
+pub fn find_syncmers_pos_par_chunked<const N: usize>(
+    k: usize,
+    s: usize,
+    ts: &[usize; N],
+    chunk_size: usize,
+    seq: &[u8],
+) -> Vec<usize> {
+    assert!(seq.len() > k);
+    assert!(chunk_size > 0);
+
+    // find_syncmers_pos requires more than k bases, so every chunk needs at least 2 kmers
+    let chunk_size = chunk_size.max(2);
+    let kmers = seq.len() - k + 1;
+    if kmers <= chunk_size {
+        return find_syncmers_pos(k, s, ts, seq);
+    }
+
+    // A single kmer tail is folded into the chunk before it
+    let mut starts = (0..kmers).step_by(chunk_size).collect::<Vec<_>>();
+    if kmers - starts[starts.len() - 1] < 2 {
+        starts.pop();
+    }
+
+    let chunks = starts
+        .par_iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(kmers);
+            let mut positions = find_syncmers_pos(k, s, ts, &seq[start..end + k - 1]);
+            positions.iter_mut().for_each(|pos| *pos += start);
+            positions
+        })
+        .collect::<Vec<_>>();
+
+    let mut positions = Vec::with_capacity(chunks.iter().map(|x| x.len()).sum());
+    chunks.into_iter().for_each(|x| positions.extend(x));
+    positions
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::random_seq;
+
+    #[test]
+    pub fn test_par_matches_serial_at_seams() {
+        let seq = random_seq(10_000, 0x9E3779B97F4A7C15);
+
+        let serial = find_syncmers_pos(31, 11, &[0, 20], &seq);
+        for chunk_size in [1, 2, 7, 30, 31, 32, 1000, 9969, 9970, 20_000] {
+            let par = find_syncmers_pos_par_chunked(31, 11, &[0, 20], chunk_size, &seq);
+            assert_eq!(serial, par, "chunk_size {}", chunk_size);
+        }
+    }
+}
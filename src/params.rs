@@ -0,0 +1,333 @@
+//! Runtime syncmer parameters, shared by sketches and (later) indices and files
+//!
+//! find_syncmers / find_syncmers_pos take ts as a const generic array, which is fastest but
+//! awkward to store. Params holds validated k, s and ts along with the hash seed and whether
+//! kmers are canonicalized, so results built from it can be checked for compatibility.
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use xxhash_rust::const_xxh3::const_custom_default_secret;
+use xxhash_rust::xxh3::xxh3_64_with_secret;
+
+use crate::{complement, smallest_smer_pos, Error};
+
+/// Seed used by find_syncmers for downsampling. Sketches with this seed share its hashes.
+pub const DEFAULT_SEED: u64 = 42;
+
+/// Strand a syncmer was taken from. Always Forward unless canonical kmers are enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+/// A syncmer occurrence: position of the kmer on the forward strand, the strand the
+/// (canonical) kmer was read from, and its hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Syncmer {
+    pub pos: usize,
+    pub strand: Strand,
+    pub hash: u64,
+}
+
+/// Validated syncmer parameters
+///
+/// ```rust
+/// # use syncmers::{find_syncmers_pos, Params};
+/// let params = Params::new(5, 2, &[2]).unwrap();
+/// let sequence = b"CCAGTGTTTACGG";
+/// let positions = params.syncmers(sequence).iter().map(|x| x.pos).collect::<Vec<_>>();
+/// assert!(positions == find_syncmers_pos(5, 2, &[2], sequence));
+/// ```
+///
+/// With the `serde` feature, Params serializes as its k, s, ts, seed and canonical fields.
+/// Deserializing validates k, s and ts again, as [`Params::new`] does.
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "ParamsRepr", into = "ParamsRepr")
+)]
+pub struct Params {
+    k: usize,
+    s: usize,
+    ts: Vec<usize>,
+    seed: u64,
+    canonical: bool,
+    secret: [u8; 192],
+}
+
+impl Params {
+    /// k: kmer length
+    /// s: smer length
+    /// ts: Target positions (0-based). Smallest smer must appear in one of these positions
+    pub fn new(k: usize, s: usize, ts: &[usize]) -> Result<Self, Error> {
+        if s == 0 || s >= k {
+            return Err(Error::InvalidParams(format!(
+                "s must be between 1 and k - 1 (k = {}, s = {})",
+                k, s
+            )));
+        }
+        if ts.is_empty() {
+            return Err(Error::InvalidParams("ts must not be empty".to_string()));
+        }
+        if let Some(t) = ts.iter().find(|&&t| t > k - s) {
+            return Err(Error::InvalidParams(format!(
+                "Target position {} is past the last smer (k - s = {})",
+                t,
+                k - s
+            )));
+        }
+
+        let mut ts = ts.to_vec();
+        ts.sort_unstable();
+        ts.dedup();
+
+        Ok(Params {
+            k,
+            s,
+            ts,
+            seed: DEFAULT_SEED,
+            canonical: false,
+            secret: const_custom_default_secret(DEFAULT_SEED),
+        })
+    }
+
+    /// Open syncmers: smallest smer at the start of the kmer
+    pub fn open(k: usize, s: usize) -> Result<Self, Error> {
+        Params::new(k, s, &[0])
+    }
+
+    /// Closed syncmers: smallest smer at the start or end of the kmer
+    pub fn closed(k: usize, s: usize) -> Result<Self, Error> {
+        Params::new(k, s, &[0, k.saturating_sub(s)])
+    }
+
+    /// Seed for hashing syncmers
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.secret = const_custom_default_secret(seed);
+        self
+    }
+
+    /// Use canonical kmers: the smaller of the kmer and its reverse complement is tested and
+    /// hashed, so both strands of a sequence give the same syncmers
+    pub fn with_canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    pub fn s(&self) -> usize {
+        self.s
+    }
+
+    pub fn ts(&self) -> &[usize] {
+        &self.ts
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn canonical(&self) -> bool {
+        self.canonical
+    }
+
+    /// Error unless other was built with the same parameters
+    pub fn check_compatible(&self, other: &Params) -> Result<(), Error> {
+        if self == other {
+            Ok(())
+        } else {
+            Err(Error::IncompatibleParams(format!(
+                "{:?} vs {:?}",
+                self, other
+            )))
+        }
+    }
+
+    /// Hash a kmer (xxh3 with this seed's secret)
+    #[inline]
+    pub fn hash(&self, kmer: &[u8]) -> u64 {
+        xxh3_64_with_secret(kmer, &self.secret)
+    }
+
+    /// Whether the smallest smer of kmer is at one of the target positions.
+    /// kmer is tested as given, it is not canonicalized
+    #[inline]
+    pub fn is_syncmer(&self, kmer: &[u8]) -> bool {
+        self.ts.contains(&smallest_smer_pos(kmer, self.s))
+    }
+
+    /// Find all syncmers of seq, in order of position
+    pub fn syncmers(&self, seq: &[u8]) -> Vec<Syncmer> {
+        let mut syncmers = Vec::new();
+        self.for_each_syncmer(seq, |x| syncmers.push(x));
+        syncmers
+    }
+
+    /// Call f for each syncmer of seq, in order of position
+    pub fn for_each_syncmer<F: FnMut(Syncmer)>(&self, seq: &[u8], mut f: F) {
+        let mut rc = vec![0; self.k];
+        for (pos, kmer) in seq.windows(self.k).enumerate() {
+            let (kmer, strand, offset) = self.select(kmer, &mut rc);
+            if self.ts.contains(&offset) {
+                f(Syncmer {
+                    pos,
+                    strand,
+                    hash: self.hash(kmer),
+                });
+            }
+        }
+    }
+
+    /// The kmer as tested (the smaller of it and its reverse complement, written into rc, when
+    /// canonical), its strand and the offset of its smallest smer. rc must be k long
+    pub(crate) fn select<'a>(&self, kmer: &'a [u8], rc: &'a mut [u8]) -> (&'a [u8], Strand, usize) {
+        if !self.canonical {
+            return (kmer, Strand::Forward, smallest_smer_pos(kmer, self.s));
+        }
+
+        rc.iter_mut().zip(kmer.iter().rev()).for_each(|(x, &c)| {
+            *x = c;
+            complement(x);
+        });
+        let (kmer, strand) = if &*rc < kmer {
+            (&*rc, Strand::Reverse)
+        } else {
+            (kmer, Strand::Forward)
+        };
+        (kmer, strand, smallest_smer_pos(kmer, self.s))
+    }
+}
+
+/// Serialized form of Params, validated on the way back in
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct ParamsRepr {
+    k: usize,
+    s: usize,
+    ts: Vec<usize>,
+    #[serde(default = "default_seed")]
+    seed: u64,
+    #[serde(default)]
+    canonical: bool,
+}
+
+#[cfg(feature = "serde")]
+fn default_seed() -> u64 {
+    DEFAULT_SEED
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<ParamsRepr> for Params {
+    type Error = Error;
+
+    fn try_from(repr: ParamsRepr) -> Result<Self, Error> {
+        Ok(Params::new(repr.k, repr.s, &repr.ts)?
+            .with_seed(repr.seed)
+            .with_canonical(repr.canonical))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Params> for ParamsRepr {
+    fn from(params: Params) -> Self {
+        ParamsRepr {
+            k: params.k,
+            s: params.s,
+            ts: params.ts,
+            seed: params.seed,
+            canonical: params.canonical,
+        }
+    }
+}
+
+impl PartialEq for Params {
+    fn eq(&self, other: &Self) -> bool {
+        self.k == other.k
+            && self.s == other.s
+            && self.ts == other.ts
+            && self.seed == other.seed
+            && self.canonical == other.canonical
+    }
+}
+
+impl Eq for Params {}
+
+impl fmt::Debug for Params {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Params")
+            .field("k", &self.k)
+            .field("s", &self.s)
+            .field("ts", &self.ts)
+            .field("seed", &self.seed)
+            .field("canonical", &self.canonical)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::revcomp;
+
+    #[test]
+    pub fn test_params_validation() {
+        assert!(Params::new(5, 2, &[2]).is_ok());
+        assert!(Params::new(5, 5, &[0]).is_err());
+        assert!(Params::new(5, 0, &[0]).is_err());
+        assert!(Params::new(5, 2, &[4]).is_err());
+        assert!(Params::new(5, 2, &[]).is_err());
+        assert_eq!(Params::closed(31, 15).unwrap().ts(), &[0, 16]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    pub fn test_params_serde() {
+        let params = Params::new(21, 11, &[0, 10]).unwrap().with_seed(7);
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(
+            json,
+            r#"{"k":21,"s":11,"ts":[0,10],"seed":7,"canonical":false}"#
+        );
+        assert_eq!(serde_json::from_str::<Params>(&json).unwrap(), params);
+
+        let defaults = serde_json::from_str::<Params>(r#"{"k":5,"s":2,"ts":[2]}"#).unwrap();
+        assert_eq!(defaults, Params::new(5, 2, &[2]).unwrap());
+
+        assert!(serde_json::from_str::<Params>(r#"{"k":5,"s":2,"ts":[4]}"#).is_err());
+        assert!(serde_json::from_str::<Params>(r#"{"k":5,"s":6,"ts":[0]}"#).is_err());
+    }
+
+    #[test]
+    pub fn test_canonical_strand_independent() {
+        let params = Params::new(15, 5, &[2, 7]).unwrap().with_canonical(true);
+        let seq = b"ACGATCGATCGGGATCTAGCTAGCTAGGCTAGCATCGACTTTAGCTAGC".to_vec();
+        let mut rc = seq.clone();
+        revcomp(&mut rc);
+
+        let mut fwd = params
+            .syncmers(&seq)
+            .iter()
+            .map(|x| x.hash)
+            .collect::<Vec<_>>();
+        let mut rev = params
+            .syncmers(&rc)
+            .iter()
+            .map(|x| x.hash)
+            .collect::<Vec<_>>();
+        fwd.sort_unstable();
+        rev.sort_unstable();
+        assert!(!fwd.is_empty());
+        assert_eq!(fwd, rev);
+    }
+}
@@ -0,0 +1,31 @@
+//! Xorshift generator for mutating sequences, calibrating parameters on random sequence and
+//! the test fixtures. Not for anything needing statistical quality beyond that
+
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    /// A zero seed, which xorshift can't leave, is replaced by 1
+    pub(crate) fn new(seed: u64) -> Self {
+        XorShift(seed.max(1))
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniform in [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Uniformly random ACGT sequence
+pub(crate) fn random_bases(len: usize, seed: u64) -> Vec<u8> {
+    let mut rng = XorShift::new(seed);
+    (0..len)
+        .map(|_| b"ACGT"[(rng.next_u64() % 4) as usize])
+        .collect()
+}
@@ -0,0 +1,704 @@
+//! FracMinHash sketches of syncmers
+//!
+//! A sketch keeps every syncmer hash below u64::MAX / scaled, the same threshold find_syncmers
+//! applies with downsample = Some(1.0 / scaled). With the default seed (42) and non-canonical
+//! params, a sketch contains exactly the hashes of the syncmers find_syncmers would return.
+//!
+//! Because the threshold is fixed, sketches of different sequences are directly comparable and
+//! estimate Jaccard, containment and ANI without an external tool.
+//!
+//! [`BottomKSketch`] instead keeps a fixed number of the smallest hashes (Mash style), for
+//! database entries that shouldn't grow with genome length.
+
+use std::collections::BTreeSet;
+use std::io::Read;
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::fasta::FastaReader;
+use crate::format::{open_compatible, Header, RecordKind, SyncmerReader, SyncmerWriter};
+use crate::{Error, Params};
+
+/// Sorted, deduplicated syncmer hashes below the scaled threshold
+///
+/// With the `serde` feature, deserialized sketches are checked: params are validated, scaled
+/// must be at least 1, and hashes are re-sorted and filtered to the threshold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "SketchRepr")
+)]
+pub struct Sketch {
+    params: Params,
+    scaled: u64,
+    hashes: Vec<u64>,
+}
+
+/// Hash threshold for a scaled value, as computed by find_syncmers for downsample = 1 / scaled
+pub fn max_hash(scaled: u64) -> u64 {
+    (u64::MAX as f64 * (1.0 / scaled as f64)) as u64
+}
+
+impl Sketch {
+    /// Empty sketch. scaled = 1 keeps (almost) every syncmer
+    pub fn new(params: Params, scaled: u64) -> Self {
+        assert!(scaled >= 1);
+        Sketch {
+            params,
+            scaled,
+            hashes: Vec::new(),
+        }
+    }
+
+    /// Sketch a single sequence
+    ///
+    /// ```rust
+    /// # use syncmers::{Params, Sketch};
+    /// let params = Params::new(21, 11, &[0]).unwrap();
+    /// let sketch = Sketch::from_sequence(params, 1, b"CCAGTGTTTACGGCCAGTGTTTACGGCCAGTGTTTACGG");
+    /// assert!(!sketch.is_empty());
+    /// assert!(sketch.jaccard(&sketch).unwrap() == 1.0);
+    /// ```
+    pub fn from_sequence(params: Params, scaled: u64, seq: &[u8]) -> Self {
+        let mut sketch = Sketch::new(params, scaled);
+        sketch.add_sequence(seq);
+        sketch
+    }
+
+    /// Sketch every record of a FASTA / FASTQ file into a single sketch
+    pub fn from_fasta<P: AsRef<Path>>(params: Params, scaled: u64, path: P) -> Result<Self, Error> {
+        let mut sketch = Sketch::new(params, scaled);
+        for record in FastaReader::from_path(path)? {
+            sketch.add_sequence(&record?.seq);
+        }
+        Ok(sketch)
+    }
+
+    /// Build a sketch from hashes computed elsewhere. Hashes above the threshold are dropped
+    pub fn from_hashes(params: Params, scaled: u64, hashes: Vec<u64>) -> Self {
+        let mut sketch = Sketch::new(params, scaled);
+        sketch.extend(hashes);
+        sketch
+    }
+
+    /// Add the syncmers of another sequence
+    pub fn add_sequence(&mut self, seq: &[u8]) {
+        let max_hash = self.max_hash();
+        let mut hashes = Vec::new();
+        self.params.for_each_syncmer(seq, |x| {
+            if x.hash < max_hash {
+                hashes.push(x.hash);
+            }
+        });
+        self.extend(hashes);
+    }
+
+    fn extend(&mut self, hashes: Vec<u64>) {
+        let max_hash = self.max_hash();
+        self.hashes
+            .extend(hashes.into_iter().filter(|&x| x < max_hash));
+        self.hashes.sort_unstable();
+        self.hashes.dedup();
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn scaled(&self) -> u64 {
+        self.scaled
+    }
+
+    /// Hashes at or above this value are not kept
+    pub fn max_hash(&self) -> u64 {
+        max_hash(self.scaled)
+    }
+
+    /// Sorted, deduplicated hashes
+    pub fn hashes(&self) -> &[u64] {
+        &self.hashes
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Error unless other was built with the same params and scaled
+    pub fn check_compatible(&self, other: &Sketch) -> Result<(), Error> {
+        self.params.check_compatible(&other.params)?;
+        if self.scaled != other.scaled {
+            return Err(Error::IncompatibleParams(format!(
+                "scaled {} vs {}, downsample one of the sketches first",
+                self.scaled, other.scaled
+            )));
+        }
+        Ok(())
+    }
+
+    /// Copy of this sketch at a coarser (larger) scaled value
+    pub fn downsample(&self, scaled: u64) -> Result<Sketch, Error> {
+        if scaled < self.scaled {
+            return Err(Error::InvalidParams(format!(
+                "Can't downsample from scaled {} to the finer scaled {}",
+                self.scaled, scaled
+            )));
+        }
+        let max_hash = max_hash(scaled);
+        Ok(Sketch {
+            params: self.params.clone(),
+            scaled,
+            hashes: self
+                .hashes
+                .iter()
+                .copied()
+                .filter(|&x| x < max_hash)
+                .collect(),
+        })
+    }
+
+    /// Merge other into this sketch (in place union)
+    pub fn merge(&mut self, other: &Sketch) -> Result<(), Error> {
+        self.check_compatible(other)?;
+        let hashes = std::mem::take(&mut self.hashes);
+        self.hashes = union_sorted(&hashes, &other.hashes);
+        Ok(())
+    }
+
+    /// New sketch of the union of both
+    pub fn union(&self, other: &Sketch) -> Result<Sketch, Error> {
+        let mut sketch = self.clone();
+        sketch.merge(other)?;
+        Ok(sketch)
+    }
+
+    /// Number of hashes present in both sketches
+    pub fn intersection_size(&self, other: &Sketch) -> Result<usize, Error> {
+        self.check_compatible(other)?;
+        Ok(intersection_size_sorted(&self.hashes, &other.hashes))
+    }
+
+    /// Jaccard index |A ∩ B| / |A ∪ B|
+    pub fn jaccard(&self, other: &Sketch) -> Result<f64, Error> {
+        let shared = self.intersection_size(other)?;
+        let union = self.len() + other.len() - shared;
+        if union == 0 {
+            return Ok(0.0);
+        }
+        Ok(shared as f64 / union as f64)
+    }
+
+    /// Fraction of this sketch found in other, |A ∩ B| / |A|
+    pub fn containment(&self, other: &Sketch) -> Result<f64, Error> {
+        let shared = self.intersection_size(other)?;
+        if self.is_empty() {
+            return Ok(0.0);
+        }
+        Ok(shared as f64 / self.len() as f64)
+    }
+
+    /// Average nucleotide identity estimated from the Jaccard index (1 - Mash distance)
+    pub fn ani(&self, other: &Sketch) -> Result<f64, Error> {
+        Ok(1.0 - mash_distance(self.jaccard(other)?, self.params.k()))
+    }
+
+    /// Average nucleotide identity of this sketch within other, estimated from containment
+    /// as C^(1/k). Better than [`Sketch::ani`] when the genomes differ in size
+    pub fn containment_ani(&self, other: &Sketch) -> Result<f64, Error> {
+        Ok(self.containment(other)?.powf(1.0 / self.params.k() as f64))
+    }
+}
+
+/// Write named sketches to a file in the [`crate::format`] layout. All sketches must share
+/// params and scaled
+pub fn save_sketches<P: AsRef<Path>>(path: P, sketches: &[(String, Sketch)]) -> Result<(), Error> {
+    let first = match sketches.first() {
+        Some((_, first)) => first,
+        None => {
+            return Err(Error::InvalidParams(
+                "No sketches to write, a file needs their parameters".to_string(),
+            ))
+        }
+    };
+
+    let header = Header::new(RecordKind::Hashes, first.params.clone(), first.scaled);
+    let mut writer = SyncmerWriter::create(path, header)?;
+    for (name, sketch) in sketches {
+        first.check_compatible(sketch)?;
+        writer.write_record(name, sketch.hashes())?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Read named sketches written by [`save_sketches`]
+pub fn load_sketches<P: AsRef<Path>>(path: P) -> Result<Vec<(String, Sketch)>, Error> {
+    read_sketches(SyncmerReader::open(path)?)
+}
+
+/// Read sketches from several files, refusing files built with different parameters (compared
+/// on the file headers, so files without records are checked too)
+pub fn load_sketch_files<P: AsRef<Path>>(paths: &[P]) -> Result<Vec<(String, Sketch)>, Error> {
+    let mut sketches = Vec::new();
+    for reader in open_compatible(paths)? {
+        sketches.extend(read_sketches(reader)?);
+    }
+    Ok(sketches)
+}
+
+fn read_sketches<R: Read>(reader: SyncmerReader<R>) -> Result<Vec<(String, Sketch)>, Error> {
+    let header = reader.header().clone();
+    if header.kind != RecordKind::Hashes {
+        return Err(Error::Format(
+            "File holds syncmer positions, not sketches".to_string(),
+        ));
+    }
+
+    reader
+        .map(|record| {
+            let record = record?;
+            Ok((
+                record.name,
+                Sketch::from_hashes(header.params.clone(), header.scaled, record.values),
+            ))
+        })
+        .collect()
+}
+
+/// Bottom-k MinHash sketch: the `size` smallest syncmer hashes, plus the number of kmers
+/// sketched (needed for Mash p-values)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "BottomKSketchRepr")
+)]
+pub struct BottomKSketch {
+    params: Params,
+    size: usize,
+    length: u64,
+    hashes: Vec<u64>,
+}
+
+impl BottomKSketch {
+    /// Empty sketch keeping at most size hashes
+    pub fn new(params: Params, size: usize) -> Self {
+        assert!(size > 0);
+        BottomKSketch {
+            params,
+            size,
+            length: 0,
+            hashes: Vec::new(),
+        }
+    }
+
+    /// Sketch a single sequence
+    ///
+    /// ```rust
+    /// # use syncmers::{BottomKSketch, Params};
+    /// let params = Params::new(15, 5, &[0]).unwrap();
+    /// let sketch = BottomKSketch::from_sequence(params, 1000, b"CCAGTGTTTACGGCCAGTGTTTACGGCCAGTGTTTACGG");
+    /// assert!(sketch.distance(&sketch).unwrap() == 0.0);
+    /// ```
+    pub fn from_sequence(params: Params, size: usize, seq: &[u8]) -> Self {
+        let mut sketch = BottomKSketch::new(params, size);
+        sketch.add_sequence(seq);
+        sketch
+    }
+
+    /// Sketch every record of a FASTA / FASTQ file into a single sketch
+    pub fn from_fasta<P: AsRef<Path>>(params: Params, size: usize, path: P) -> Result<Self, Error> {
+        let mut sketch = BottomKSketch::new(params, size);
+        for record in FastaReader::from_path(path)? {
+            sketch.add_sequence(&record?.seq);
+        }
+        Ok(sketch)
+    }
+
+    /// Add the syncmers of another sequence
+    pub fn add_sequence(&mut self, seq: &[u8]) {
+        let size = self.size;
+        let mut bottom = self.hashes.drain(..).collect::<BTreeSet<u64>>();
+        self.params.for_each_syncmer(seq, |x| {
+            if bottom.len() < size || x.hash < *bottom.last().unwrap() {
+                bottom.insert(x.hash);
+                if bottom.len() > size {
+                    bottom.pop_last();
+                }
+            }
+        });
+        self.hashes = bottom.into_iter().collect();
+        self.length += (seq.len() + 1).saturating_sub(self.params.k()) as u64;
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// Maximum number of hashes kept
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of kmers sketched
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Sorted, deduplicated hashes
+    pub fn hashes(&self) -> &[u64] {
+        &self.hashes
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Merge other into this sketch, e.g. sketches of different chunks of one genome.
+    /// Lengths are summed, so kmers spanning chunk boundaries are not counted. Both sketches
+    /// must have the same size: the merge of a smaller one is not a bottom sketch of the union
+    pub fn merge(&mut self, other: &BottomKSketch) -> Result<(), Error> {
+        self.params.check_compatible(&other.params)?;
+        if self.size != other.size {
+            return Err(Error::IncompatibleParams(format!(
+                "size {} vs {}",
+                self.size, other.size
+            )));
+        }
+        let mut merged = union_sorted(&self.hashes, &other.hashes);
+        merged.truncate(self.size);
+        self.hashes = merged;
+        self.length += other.length;
+        Ok(())
+    }
+
+    /// Shared hashes among the bottom hashes of the union, and the number of union hashes
+    /// considered (the smaller of the two sizes)
+    fn shared(&self, other: &BottomKSketch) -> Result<(usize, usize), Error> {
+        self.params.check_compatible(&other.params)?;
+        let size = self.size.min(other.size);
+        let mut union = union_sorted(&self.hashes, &other.hashes);
+        union.truncate(size);
+        let shared = union
+            .iter()
+            .filter(|x| {
+                self.hashes.binary_search(x).is_ok() && other.hashes.binary_search(x).is_ok()
+            })
+            .count();
+        Ok((shared, union.len()))
+    }
+
+    /// Jaccard index estimated from the bottom hashes of the union
+    pub fn jaccard(&self, other: &BottomKSketch) -> Result<f64, Error> {
+        let (shared, total) = self.shared(other)?;
+        if total == 0 {
+            return Ok(0.0);
+        }
+        Ok(shared as f64 / total as f64)
+    }
+
+    /// Mash distance
+    pub fn distance(&self, other: &BottomKSketch) -> Result<f64, Error> {
+        Ok(mash_distance(self.jaccard(other)?, self.params.k()))
+    }
+
+    /// Probability of seeing at least this many shared hashes between two random sequences of
+    /// the same lengths (Mash p-value, Ondov et al. 2016)
+    pub fn p_value(&self, other: &BottomKSketch) -> Result<f64, Error> {
+        let (shared, total) = self.shared(other)?;
+        Ok(mash_p_value(
+            shared,
+            total,
+            self.params.k(),
+            self.length,
+            other.length,
+        ))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct SketchRepr {
+    params: Params,
+    scaled: u64,
+    hashes: Vec<u64>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SketchRepr> for Sketch {
+    type Error = Error;
+
+    fn try_from(repr: SketchRepr) -> Result<Self, Error> {
+        if repr.scaled == 0 {
+            return Err(Error::InvalidParams(
+                "scaled must be at least 1".to_string(),
+            ));
+        }
+        Ok(Sketch::from_hashes(repr.params, repr.scaled, repr.hashes))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct BottomKSketchRepr {
+    params: Params,
+    size: usize,
+    length: u64,
+    hashes: Vec<u64>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<BottomKSketchRepr> for BottomKSketch {
+    type Error = Error;
+
+    fn try_from(mut repr: BottomKSketchRepr) -> Result<Self, Error> {
+        if repr.size == 0 {
+            return Err(Error::InvalidParams("size must be at least 1".to_string()));
+        }
+        repr.hashes.sort_unstable();
+        repr.hashes.dedup();
+        repr.hashes.truncate(repr.size);
+        Ok(BottomKSketch {
+            params: repr.params,
+            size: repr.size,
+            length: repr.length,
+            hashes: repr.hashes,
+        })
+    }
+}
+
+/// Mash p-value for `shared` of `total` sketch hashes matching between sequences with
+/// length_a and length_b kmers. Syncmer selection thins the sequences and the kmer universe
+/// by the same density, so the kmer-based random match probability is unchanged
+pub fn mash_p_value(shared: usize, total: usize, k: usize, length_a: u64, length_b: u64) -> f64 {
+    if shared == 0 {
+        return 1.0;
+    }
+
+    let universe = 4f64.powi(k as i32);
+    let pa = 1.0 / (1.0 + universe / length_a as f64);
+    let pb = 1.0 / (1.0 + universe / length_b as f64);
+    let r = pa * pb / (pa + pb - pa * pb);
+    if r <= 0.0 || r.is_nan() {
+        return 0.0;
+    }
+
+    // 1 - P(X < shared) for X ~ Binomial(total, r), summed in log space
+    let ln_r = r.ln();
+    let ln_1r = (-r).ln_1p();
+    let mut ln_choose = 0.0;
+    let mut cdf = 0.0;
+    for i in 0..shared {
+        if i > 0 {
+            ln_choose += ((total - i + 1) as f64).ln() - (i as f64).ln();
+        }
+        cdf += (ln_choose + i as f64 * ln_r + (total - i) as f64 * ln_1r).exp();
+    }
+    (1.0 - cdf).max(0.0)
+}
+
+/// Mash distance from a Jaccard index, -1/k ln(2j / (1 + j)), capped at 1
+pub fn mash_distance(jaccard: f64, k: usize) -> f64 {
+    if jaccard <= 0.0 {
+        return 1.0;
+    }
+    let d = -1.0 / k as f64 * (2.0 * jaccard / (1.0 + jaccard)).ln();
+    d.clamp(0.0, 1.0)
+}
+
+pub(crate) fn union_sorted(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => {
+                merged.push(a[i]);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                merged.push(b[j]);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                merged.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    merged.extend_from_slice(&a[i..]);
+    merged.extend_from_slice(&b[j..]);
+    merged
+}
+
+pub(crate) fn intersection_size_sorted(a: &[u64], b: &[u64]) -> usize {
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                shared += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    shared
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::find_syncmers;
+    use crate::test_util::random_seq;
+
+    #[test]
+    pub fn test_sketch_matches_find_syncmers_downsample() {
+        let seq = random_seq(20_000, 7);
+        let params = Params::new(21, 11, &[0, 10]).unwrap();
+        let sketch = Sketch::from_sequence(params.clone(), 5, &seq);
+
+        let mut expected = find_syncmers(21, 11, &[0, 10], Some(0.2), &seq)
+            .iter()
+            .map(|x| params.hash(x))
+            .collect::<Vec<_>>();
+        expected.sort_unstable();
+        expected.dedup();
+        assert_eq!(sketch.hashes(), expected.as_slice());
+    }
+
+    #[test]
+    pub fn test_sketch_comparisons() {
+        let a = random_seq(50_000, 11);
+        let mut b = a.clone();
+        // ~1% substitutions
+        for i in (0..b.len()).step_by(100) {
+            b[i] = if b[i] == b'A' { b'C' } else { b'A' };
+        }
+        let params = Params::new(21, 11, &[5]).unwrap().with_canonical(true);
+        let sa = Sketch::from_sequence(params.clone(), 1, &a);
+        let sb = Sketch::from_sequence(params.clone(), 1, &b);
+
+        let ani = sa.ani(&sb).unwrap();
+        assert!(ani > 0.98 && ani < 1.0, "ani {}", ani);
+        assert!(sa.containment_ani(&sb).unwrap() > 0.98);
+
+        let union = sa.union(&sb).unwrap();
+        assert_eq!(
+            union.len(),
+            sa.len() + sb.len() - sa.intersection_size(&sb).unwrap()
+        );
+        assert_eq!(
+            union.containment(&sa).unwrap(),
+            sa.len() as f64 / union.len() as f64
+        );
+
+        let other = Sketch::new(Params::new(21, 11, &[6]).unwrap(), 1);
+        assert!(sa.jaccard(&other).is_err());
+        assert!(sa.jaccard(&sa.downsample(10).unwrap()).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    pub fn test_sketch_serde() {
+        let params = Params::new(21, 11, &[5]).unwrap();
+        let sketch = Sketch::from_sequence(params.clone(), 4, &random_seq(5_000, 29));
+        let json = serde_json::to_string(&sketch).unwrap();
+        assert_eq!(serde_json::from_str::<Sketch>(&json).unwrap(), sketch);
+
+        let bottom = BottomKSketch::from_sequence(params, 50, &random_seq(5_000, 29));
+        let json = serde_json::to_string(&bottom).unwrap();
+        assert_eq!(
+            serde_json::from_str::<BottomKSketch>(&json).unwrap(),
+            bottom
+        );
+
+        let bad = r#"{"params":{"k":21,"s":11,"ts":[5]},"scaled":0,"hashes":[]}"#;
+        assert!(serde_json::from_str::<Sketch>(bad).is_err());
+    }
+
+    #[test]
+    pub fn test_sketch_files() {
+        let dir = std::env::temp_dir().join(format!("syncmers-sketch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let params = Params::new(21, 11, &[5]).unwrap();
+        let a = Sketch::from_sequence(params.clone(), 2, &random_seq(5_000, 19));
+        let b = Sketch::from_sequence(params.clone(), 2, &random_seq(5_000, 23));
+        let c = Sketch::from_sequence(params.clone().with_seed(1), 2, &random_seq(5_000, 23));
+
+        let ab = dir.join("ab.sk");
+        save_sketches(
+            &ab,
+            &[("a".to_string(), a.clone()), ("b".to_string(), b.clone())],
+        )
+        .unwrap();
+        let loaded = load_sketches(&ab).unwrap();
+        assert_eq!(loaded, vec![("a".to_string(), a), ("b".to_string(), b)]);
+
+        let other = dir.join("c.sk");
+        save_sketches(&other, &[("c".to_string(), c)]).unwrap();
+        assert!(matches!(
+            load_sketch_files(&[&ab, &other]),
+            Err(Error::IncompatibleParams(_))
+        ));
+
+        // A file without records is checked on its header
+        let empty = dir.join("empty.sk");
+        let header = Header::new(RecordKind::Hashes, params.with_seed(1), 2);
+        SyncmerWriter::create(&empty, header)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert!(matches!(
+            load_sketch_files(&[&ab, &empty]),
+            Err(Error::IncompatibleParams(_))
+        ));
+        assert_eq!(load_sketch_files(&[&empty]).unwrap(), vec![]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_bottomk_merge_and_distance() {
+        let a = random_seq(50_000, 13);
+        let params = Params::new(21, 11, &[5]).unwrap();
+        let whole = BottomKSketch::from_sequence(params.clone(), 500, &a);
+        assert_eq!(whole.len(), 500);
+
+        // Chunks overlapping by k - 1 give the same hashes as the whole sequence
+        let mut merged = BottomKSketch::from_sequence(params.clone(), 500, &a[..25_020]);
+        merged
+            .merge(&BottomKSketch::from_sequence(
+                params.clone(),
+                500,
+                &a[25_000..],
+            ))
+            .unwrap();
+        assert_eq!(merged.hashes(), whole.hashes());
+        assert_eq!(merged.distance(&whole).unwrap(), 0.0);
+
+        let unrelated = BottomKSketch::from_sequence(params.clone(), 500, &random_seq(50_000, 17));
+        assert_eq!(whole.distance(&unrelated).unwrap(), 1.0);
+        assert_eq!(whole.p_value(&unrelated).unwrap(), 1.0);
+        assert!(whole.p_value(&merged).unwrap() < 1e-100);
+
+        let smaller = BottomKSketch::from_sequence(params, 100, &a);
+        assert!(matches!(
+            merged.merge(&smaller),
+            Err(Error::IncompatibleParams(_))
+        ));
+        assert_eq!(merged.hashes(), whole.hashes());
+    }
+}
@@ -0,0 +1,279 @@
+//! Export and import of sourmash signature JSON (scaled MinHash)
+//!
+//! **These are syncmer-restricted hash sets.** A [`Sketch`] holds xxh3 hashes of syncmers only,
+//! while sourmash hashes every kmer with murmur64. The exported signatures load in sourmash and
+//! compare correctly with each other (same ksize, seed and hashing), but they must not be
+//! compared with signatures sourmash computed from sequence itself: the hash values differ, so
+//! such comparisons report no similarity.
+//!
+//! Sourmash signatures record only ksize, seed and max_hash, so exported signatures carry the
+//! rest of the scheme in their filename, as `syncmers:s=11,t=0+10,canonical:<filename>`.
+//! Importing needs the [`Params`] the signature was exported with, and refuses signatures
+//! without that mark (such as ones computed by sourmash) or with a different scheme.
+//!
+//! **Import only reads signatures this crate exported.** The JSON layout follows sourmash's
+//! signature format 0.4, but reading files written by sourmash itself is neither supported nor
+//! tested; the checked-in test signature was written by [`write_signatures`].
+
+use std::io::{Read, Write};
+
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+
+use crate::sketch::max_hash;
+use crate::{Error, Params, Sketch};
+
+/// Sourmash signature file format version written
+pub const SIGNATURE_VERSION: f64 = 0.4;
+
+#[derive(Serialize, Deserialize)]
+struct Signature {
+    class: String,
+    #[serde(default)]
+    email: String,
+    hash_function: String,
+    #[serde(default)]
+    filename: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default)]
+    license: String,
+    signatures: Vec<MinHash>,
+    version: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MinHash {
+    num: u64,
+    ksize: usize,
+    seed: u64,
+    max_hash: u64,
+    mins: Vec<u64>,
+    md5sum: String,
+    molecule: String,
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Format(format!("Invalid sourmash signature: {}", e))
+    }
+}
+
+/// md5sum as computed by sourmash: ksize, then each hash, as decimal strings
+fn md5sum(ksize: usize, mins: &[u64]) -> String {
+    let mut md5 = Md5::new();
+    md5.update(ksize.to_string());
+    for x in mins {
+        md5.update(x.to_string());
+    }
+    md5.finalize()
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect()
+}
+
+/// Prefix of the filename of exported signatures
+const MARK: &str = "syncmers:";
+
+/// Scheme of params not stored elsewhere in a signature, e.g. "s=11,t=0+10,canonical"
+fn scheme(params: &Params) -> String {
+    let ts = params
+        .ts()
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<_>>();
+    format!(
+        "s={},t={},{}",
+        params.s(),
+        ts.join("+"),
+        if params.canonical() {
+            "canonical"
+        } else {
+            "forward"
+        }
+    )
+}
+
+/// scaled value sourmash derives from max_hash
+fn scaled_from_max_hash(max_hash: u64) -> u64 {
+    if max_hash == 0 {
+        return 0;
+    }
+    (u64::MAX as f64 / max_hash as f64).round() as u64
+}
+
+fn to_signature(name: &str, filename: &str, sketch: &Sketch) -> Signature {
+    let ksize = sketch.params().k();
+    Signature {
+        class: "sourmash_signature".to_string(),
+        email: String::new(),
+        hash_function: "0.murmur64".to_string(),
+        filename: format!("{}{}:{}", MARK, scheme(sketch.params()), filename),
+        name: Some(name.to_string()),
+        license: "CC0".to_string(),
+        signatures: vec![MinHash {
+            num: 0,
+            ksize,
+            seed: sketch.params().seed(),
+            max_hash: sketch.max_hash(),
+            md5sum: md5sum(ksize, sketch.hashes()),
+            mins: sketch.hashes().to_vec(),
+            molecule: "DNA".to_string(),
+        }],
+        version: SIGNATURE_VERSION,
+    }
+}
+
+/// Write named sketches as a sourmash signature file (a JSON list of signatures)
+///
+/// ```rust
+/// # use syncmers::{Params, Sketch};
+/// # use syncmers::sourmash::{read_signatures, write_signatures};
+/// let params = Params::new(21, 11, &[0]).unwrap();
+/// let sketch = Sketch::from_sequence(params.clone(), 1, b"CCAGTGTTTACGGCCAGTGTTTACGGCCAGTGTTTACGG");
+/// let mut json = Vec::new();
+/// write_signatures(&mut json, &[("example".to_string(), sketch.clone())], "example.fasta").unwrap();
+/// let sketches = read_signatures(&json[..], &params).unwrap();
+/// assert!(sketches[0] == ("example".to_string(), sketch));
+/// ```
+pub fn write_signatures<W: Write>(
+    out: W,
+    sketches: &[(String, Sketch)],
+    filename: &str,
+) -> Result<(), Error> {
+    let signatures = sketches
+        .iter()
+        .map(|(name, sketch)| to_signature(name, filename, sketch))
+        .collect::<Vec<_>>();
+    serde_json::to_writer(out, &signatures)?;
+    Ok(())
+}
+
+/// Read a sourmash signature file (a single signature or a list) written by
+/// [`write_signatures`], returning one sketch per scaled MinHash. params must match the
+/// scheme and the ksize and seed of every MinHash. Signatures written by sourmash itself are
+/// refused
+pub fn read_signatures<R: Read>(input: R, params: &Params) -> Result<Vec<(String, Sketch)>, Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(Signature),
+        Many(Vec<Signature>),
+    }
+
+    let signatures = match serde_json::from_reader(input)? {
+        OneOrMany::One(x) => vec![x],
+        OneOrMany::Many(x) => x,
+    };
+
+    let expected = scheme(params);
+    let mut sketches = Vec::new();
+    for signature in signatures {
+        let (signature_scheme, filename) = signature
+            .filename
+            .strip_prefix(MARK)
+            .and_then(|x| x.split_once(':'))
+            .ok_or_else(|| {
+                Error::Format(format!(
+                    "{} was not exported by syncmers: its hashes aren't syncmer hashes",
+                    signature.name.as_deref().unwrap_or(&signature.filename)
+                ))
+            })?;
+        let name = signature
+            .name
+            .clone()
+            .unwrap_or_else(|| filename.to_string());
+        if signature_scheme != expected {
+            return Err(Error::IncompatibleParams(format!(
+                "{} was exported with {}, expected {}",
+                name, signature_scheme, expected
+            )));
+        }
+        for minhash in signature.signatures {
+            if minhash.num != 0 || minhash.max_hash == 0 {
+                return Err(Error::Format(format!(
+                    "{} is a num (bottom-k) MinHash, only scaled signatures are supported",
+                    name
+                )));
+            }
+            if minhash.ksize != params.k() || minhash.seed != params.seed() {
+                return Err(Error::IncompatibleParams(format!(
+                    "{} has ksize {} seed {}, expected ksize {} seed {}",
+                    name,
+                    minhash.ksize,
+                    minhash.seed,
+                    params.k(),
+                    params.seed()
+                )));
+            }
+
+            let scaled = scaled_from_max_hash(minhash.max_hash);
+            if max_hash(scaled) != minhash.max_hash {
+                return Err(Error::Format(format!(
+                    "{} has max_hash {}, which is not a whole scaled value",
+                    name, minhash.max_hash
+                )));
+            }
+            sketches.push((
+                name.clone(),
+                Sketch::from_hashes(params.clone(), scaled, minhash.mins),
+            ));
+        }
+    }
+    Ok(sketches)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Written by this crate's write_signatures from the sequence below, scaled 1, not by
+    // sourmash
+    const EXPORTED: &str = include_str!("../test_data/exported.sig");
+    const EXPORTED_SEQ: &[u8] =
+        b"ACGATCGATCGGGATCTAGCTAGCTAGGCTAGCATCGACTTTAGCTAGCATCGAGGACTAGGACTTATCGGCATCGA";
+
+    #[test]
+    pub fn test_exported_signature_roundtrip() {
+        let params = Params::new(21, 11, &[0, 10]).unwrap().with_canonical(true);
+        let sketches = read_signatures(EXPORTED.as_bytes(), &params).unwrap();
+        assert_eq!(sketches.len(), 1);
+        assert_eq!(sketches[0].0, "example");
+        assert_eq!(
+            sketches[0].1,
+            Sketch::from_sequence(params.clone(), 1, EXPORTED_SEQ)
+        );
+
+        let mut json = Vec::new();
+        write_signatures(&mut json, &sketches, "example.fasta").unwrap();
+        let written: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        let expected: serde_json::Value = serde_json::from_str(EXPORTED).unwrap();
+        assert_eq!(written, expected);
+
+        let other = Params::new(15, 11, &[0]).unwrap();
+        assert!(read_signatures(EXPORTED.as_bytes(), &other).is_err());
+        // Same ksize and seed, different scheme
+        let open = Params::new(21, 11, &[0]).unwrap().with_canonical(true);
+        assert!(matches!(
+            read_signatures(EXPORTED.as_bytes(), &open),
+            Err(Error::IncompatibleParams(_))
+        ));
+        let forward = params.clone().with_canonical(false);
+        assert!(read_signatures(EXPORTED.as_bytes(), &forward).is_err());
+
+        // Signatures computed by sourmash itself have no mark
+        let unmarked = EXPORTED.replace("syncmers:s=11,t=0+10,canonical:", "");
+        assert!(matches!(
+            read_signatures(unmarked.as_bytes(), &params),
+            Err(Error::Format(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_scaled_max_hash() {
+        // sourmash: max_hash = round(2**64 / scaled)
+        assert_eq!(max_hash(1000), 18446744073709552);
+        assert_eq!(scaled_from_max_hash(18446744073709552), 1000);
+        assert_eq!(md5sum(21, &[]).len(), 32);
+    }
+}
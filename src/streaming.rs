@@ -0,0 +1,138 @@
+//! Push-based syncmer extraction for sequences that arrive in chunks (sockets, decompressors)
+//!
+//! Only the last k-1 bases are carried between chunks, so memory use is bounded by k
+//! regardless of how much sequence has been pushed.
+
+use crate::{find_syncmers_pos, smallest_smer_pos};
+
+/// Stateful syncmer extractor. Feed it chunks of any size with [`SyncmerStream::push`]
+/// and it returns syncmer positions in global (whole stream) coordinates.
+///
+/// ```rust
+/// # use syncmers::{find_syncmers_pos, SyncmerStream};
+/// let sequence = b"CCAGTGTTTACGG";
+/// let mut stream = SyncmerStream::new(5, 2, &[2]);
+/// let mut positions = Vec::new();
+/// for chunk in sequence.chunks(3) {
+///     positions.extend(stream.push(chunk));
+/// }
+/// assert!(positions == find_syncmers_pos(5, 2, &[2], sequence));
+/// ```
+pub struct SyncmerStream<const N: usize> {
+    pub k: usize,
+    pub s: usize,
+    pub ts: [usize; N],
+    // Last k-1 bases seen, the start of any kmer crossing into the next chunk
+    tail: Vec<u8>,
+    // Global position of tail[0]
+    tail_pos: usize,
+    // Tail plus the start of the next chunk, reused across pushes
+    seam: Vec<u8>,
+}
+
+impl<const N: usize> SyncmerStream<N> {
+    pub fn new(k: usize, s: usize, ts: &[usize; N]) -> Self {
+        assert!(s < k);
+        assert!(ts.iter().all(|&t| t <= k - s));
+        assert!(N < 5);
+        SyncmerStream {
+            k,
+            s,
+            ts: *ts,
+            tail: Vec::with_capacity(k),
+            tail_pos: 0,
+            seam: Vec::with_capacity(2 * k),
+        }
+    }
+
+    /// Total number of bases pushed so far
+    pub fn position(&self) -> usize {
+        self.tail_pos + self.tail.len()
+    }
+
+    /// Start a new sequence, discarding any carried bases
+    pub fn reset(&mut self) {
+        self.tail.clear();
+        self.tail_pos = 0;
+    }
+
+    #[inline]
+    fn is_syncmer(&self, kmer: &[u8]) -> bool {
+        let min_pos = smallest_smer_pos(kmer, self.s);
+        if N == 1 {
+            self.ts[0] == min_pos
+        } else {
+            self.ts.contains(&min_pos)
+        }
+    }
+
+    /// Push the next chunk of the sequence, returning positions of syncmers completed by it
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<usize> {
+        let mut positions = Vec::new();
+        self.push_with(chunk, |pos| positions.push(pos));
+        positions
+    }
+
+    /// Push the next chunk of the sequence, calling f with the position of each syncmer
+    /// completed by it, in order
+    pub fn push_with<F: FnMut(usize)>(&mut self, chunk: &[u8], mut f: F) {
+        let k = self.k;
+        let chunk_pos = self.position();
+
+        // Kmers that start in the carried tail and end in this chunk
+        if !self.tail.is_empty() {
+            self.seam.clear();
+            self.seam.extend_from_slice(&self.tail);
+            self.seam
+                .extend_from_slice(&chunk[..chunk.len().min(k - 1)]);
+            for (i, kmer) in self.seam.windows(k).take(self.tail.len()).enumerate() {
+                if self.is_syncmer(kmer) {
+                    f(self.tail_pos + i);
+                }
+            }
+        }
+
+        // Kmers entirely within this chunk
+        if chunk.len() > k {
+            find_syncmers_pos(k, self.s, &self.ts, chunk)
+                .into_iter()
+                .for_each(|pos| f(chunk_pos + pos));
+        } else if chunk.len() == k && self.is_syncmer(chunk) {
+            f(chunk_pos);
+        }
+
+        // Carry the last k-1 bases forward
+        let end = chunk_pos + chunk.len();
+        if chunk.len() >= k - 1 {
+            self.tail.clear();
+            self.tail.extend_from_slice(&chunk[chunk.len() - (k - 1)..]);
+        } else {
+            self.tail.extend_from_slice(chunk);
+            let excess = self.tail.len().saturating_sub(k - 1);
+            self.tail.drain(..excess);
+        }
+        self.tail_pos = end - self.tail.len();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::random_seq;
+
+    #[test]
+    pub fn test_stream_matches_whole_sequence() {
+        let seq = random_seq(5_000, 0x2545F4914F6CDD1D);
+
+        let expected = find_syncmers_pos(21, 7, &[0, 14], &seq);
+        for chunk_size in [1, 2, 5, 20, 21, 22, 100, 4999, 5000] {
+            let mut stream = SyncmerStream::new(21, 7, &[0, 14]);
+            let mut positions = Vec::new();
+            for chunk in seq.chunks(chunk_size) {
+                positions.extend(stream.push(chunk));
+            }
+            assert_eq!(expected, positions, "chunk_size {}", chunk_size);
+            assert_eq!(stream.position(), seq.len());
+        }
+    }
+}
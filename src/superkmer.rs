@@ -0,0 +1,341 @@
+//! Super-kmers keyed by their selected smer, for partitioned kmer counting and graph building
+//!
+//! Each kmer selects its smallest smer (lexicographic, ties to the leftmost). With canonical
+//! params smers are compared as canonical smers, the smaller of the smer and its reverse
+//! complement, so the selection does not depend on the orientation of the kmer and both
+//! strands of a sequence select the same smers. This is not the syncmer selection in canonical
+//! mode, which takes the smallest smer of the canonical kmer and so jumps whenever the kmer
+//! orientation flips; without canonical the two are the same. A super-kmer is a maximal run
+//! of consecutive kmers selecting the same smer occurrence, so all its kmers share the key
+//! (the hash of that canonical smer) and can be sent to the same partition.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::count::{encode, revcomp_packed, MAX_PACKED_K};
+use crate::{Error, Params};
+
+/// A run of kmers sharing their selected smer: bases [start, end) of the sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuperKmer {
+    pub start: usize,
+    pub end: usize,
+    /// Start of the selected smer in the sequence
+    pub smer: usize,
+    /// Hash of the selected (canonical) smer
+    pub key: u64,
+}
+
+/// Iterator over the super-kmers of a sequence, in order. Consecutive super-kmers overlap by
+/// k - 1 bases
+///
+/// ```rust
+/// # use syncmers::Params;
+/// # use syncmers::superkmer::SuperKmers;
+/// let params = Params::new(5, 2, &[2]).unwrap();
+/// let seq = b"CCAGTGTTTACGGCCAGTGTTTACGG";
+/// let superkmers = SuperKmers::new(&params, seq).unwrap().collect::<Vec<_>>();
+/// assert_eq!(superkmers[0].start, 0);
+/// assert_eq!(superkmers.last().unwrap().end, seq.len());
+/// ```
+pub struct SuperKmers<'a> {
+    params: &'a Params,
+    seq: &'a [u8],
+    // Packed smers ordered for selection, u64::MAX for smers with bases other than ACGT
+    smers: Vec<u64>,
+    // Smer positions of the window with increasing values, front is the selected smer
+    window: VecDeque<usize>,
+    next_kmer: usize,
+    // (first kmer, selected smer) of the super-kmer being extended
+    current: Option<(usize, usize)>,
+}
+
+impl<'a> SuperKmers<'a> {
+    /// Smers are packed 2 bits per base, so s must be at most 32
+    pub fn new(params: &'a Params, seq: &'a [u8]) -> Result<Self, Error> {
+        check_superkmer_params(params)?;
+        let s = params.s();
+        let mask = if s == MAX_PACKED_K {
+            u64::MAX
+        } else {
+            (1 << (2 * s)) - 1
+        };
+
+        let mut smers = Vec::with_capacity(seq.len().saturating_sub(s - 1));
+        let (mut packed, mut valid) = (0u64, 0);
+        for (i, &base) in seq.iter().enumerate() {
+            match encode(base) {
+                Some(x) => {
+                    packed = (packed << 2 | x) & mask;
+                    valid += 1;
+                }
+                None => valid = 0,
+            }
+            if i + 1 >= s {
+                smers.push(if valid < s {
+                    u64::MAX
+                } else if params.canonical() {
+                    packed.min(revcomp_packed(packed, s))
+                } else {
+                    packed
+                });
+            }
+        }
+
+        Ok(SuperKmers {
+            params,
+            seq,
+            smers,
+            window: VecDeque::new(),
+            next_kmer: 0,
+            current: None,
+        })
+    }
+
+    fn emit(&self, (first, smer): (usize, usize), last: usize) -> SuperKmer {
+        SuperKmer {
+            start: first,
+            end: last + self.params.k(),
+            smer,
+            key: self.params.hash(&self.smers[smer].to_le_bytes()),
+        }
+    }
+}
+
+fn check_superkmer_params(params: &Params) -> Result<(), Error> {
+    if params.s() > MAX_PACKED_K {
+        return Err(Error::InvalidParams(format!(
+            "s must be at most {} for super-kmers, got {}",
+            MAX_PACKED_K,
+            params.s()
+        )));
+    }
+    Ok(())
+}
+
+impl Iterator for SuperKmers<'_> {
+    type Item = SuperKmer;
+
+    fn next(&mut self) -> Option<SuperKmer> {
+        let k = self.params.k();
+        let w = k - self.params.s() + 1;
+        let kmers = (self.seq.len() + 1).saturating_sub(k);
+
+        while self.next_kmer < kmers {
+            let i = self.next_kmer;
+            self.next_kmer += 1;
+
+            // Add the last smer of kmer i, keeping the leftmost of equal smers in front
+            let new = if i == 0 { 0..w } else { i + w - 1..i + w };
+            for p in new {
+                while self
+                    .window
+                    .back()
+                    .is_some_and(|&x| self.smers[x] > self.smers[p])
+                {
+                    self.window.pop_back();
+                }
+                self.window.push_back(p);
+            }
+            while self.window.front().is_some_and(|&x| x < i) {
+                self.window.pop_front();
+            }
+            let selected = self.window[0];
+
+            match self.current {
+                None => self.current = Some((i, selected)),
+                Some(current) if current.1 != selected => {
+                    self.current = Some((i, selected));
+                    return Some(self.emit(current, i - 1));
+                }
+                _ => {}
+            }
+        }
+
+        let current = self.current.take()?;
+        Some(self.emit(current, kmers - 1))
+    }
+}
+
+/// Writes the super-kmers of sequences into N bucket files by key, as FASTA records named
+/// `<sequence>:<start>-<end>`
+pub struct SuperKmerWriter {
+    params: Params,
+    paths: Vec<PathBuf>,
+    buckets: Vec<BufWriter<File>>,
+}
+
+impl SuperKmerWriter {
+    /// Create buckets files `bucket-<i>.fasta` in dir
+    pub fn create<P: AsRef<Path>>(params: Params, dir: P, buckets: usize) -> Result<Self, Error> {
+        check_superkmer_params(&params)?;
+        if buckets == 0 {
+            return Err(Error::InvalidParams(
+                "At least one bucket is needed".to_string(),
+            ));
+        }
+        std::fs::create_dir_all(&dir)?;
+        let paths = (0..buckets)
+            .map(|i| dir.as_ref().join(format!("bucket-{:05}.fasta", i)))
+            .collect::<Vec<_>>();
+        let buckets = paths
+            .iter()
+            .map(|x| Ok(BufWriter::new(File::create(x)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(SuperKmerWriter {
+            params,
+            paths,
+            buckets,
+        })
+    }
+
+    /// Bucket of a super-kmer key
+    pub fn bucket(&self, key: u64) -> usize {
+        ((key as u128 * self.buckets.len() as u128) >> 64) as usize
+    }
+
+    /// Split a sequence into super-kmers and append each to its bucket
+    pub fn write_sequence(&mut self, name: &str, seq: &[u8]) -> Result<(), Error> {
+        for superkmer in SuperKmers::new(&self.params, seq)? {
+            let bucket = self.bucket(superkmer.key);
+            let out = &mut self.buckets[bucket];
+            writeln!(out, ">{}:{}-{}", name, superkmer.start, superkmer.end)?;
+            out.write_all(&seq[superkmer.start..superkmer.end])?;
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Flush the buckets, returning their paths
+    pub fn finish(self) -> Result<Vec<PathBuf>, Error> {
+        for bucket in self.buckets {
+            bucket.into_inner().map_err(|e| e.into_error())?;
+        }
+        Ok(self.paths)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fasta::FastaReader;
+    use crate::revcomp;
+    use crate::test_util::random_seq;
+
+    /// Position of the selected smer of the kmer at i, by brute force
+    fn selected(params: &Params, seq: &[u8], i: usize) -> usize {
+        let s = params.s();
+        let ordered = |p: usize| {
+            let smer = seq[p..p + s].to_vec();
+            let mut rc = smer.clone();
+            revcomp(&mut rc);
+            if params.canonical() {
+                smer.min(rc)
+            } else {
+                smer
+            }
+        };
+        (i..i + params.k() - s + 1)
+            .min_by_key(|&p| ordered(p))
+            .unwrap()
+    }
+
+    #[test]
+    pub fn test_superkmers() {
+        let seq = random_seq(20_000, 37);
+        for canonical in [true, false] {
+            let params = Params::new(21, 11, &[5]).unwrap().with_canonical(canonical);
+            let superkmers = SuperKmers::new(&params, &seq).unwrap().collect::<Vec<_>>();
+            // A bit over 2 / (w + 1) of the kmers start a super-kmer with lexicographic
+            // order, so super-kmers hold over 4.5 kmers (about 2 if the selection followed
+            // the orientation of each kmer)
+            let kmers = seq.len() - 20;
+            assert!(
+                kmers as f64 / superkmers.len() as f64 > 4.5,
+                "{} super-kmers",
+                superkmers.len()
+            );
+            assert_eq!(superkmers[0].start, 0);
+            assert_eq!(superkmers.last().unwrap().end, seq.len());
+            for pair in superkmers.windows(2) {
+                assert_eq!(pair[1].start, pair[0].end - 20);
+                assert_ne!(pair[0].smer, pair[1].smer);
+            }
+            for x in &superkmers {
+                assert!((x.start..x.end - 20).all(|i| selected(&params, &seq, i) == x.smer));
+            }
+
+            // Without canonical, the selection is the syncmers'
+            if !canonical {
+                let found = superkmers
+                    .iter()
+                    .flat_map(|x| (x.start..x.end - 20).filter(move |&i| x.smer - i == 5))
+                    .collect::<Vec<_>>();
+                let syncmers = params
+                    .syncmers(&seq)
+                    .into_iter()
+                    .map(|x| x.pos)
+                    .collect::<Vec<_>>();
+                assert_eq!(found, syncmers);
+            }
+        }
+
+        // Both strands send every kmer to the same partition (a tie between equal smers can
+        // move a boundary, but not a key)
+        let params = Params::new(21, 11, &[5]).unwrap().with_canonical(true);
+        let mut rc = seq.clone();
+        revcomp(&mut rc);
+        let keys = |seq: &[u8]| {
+            SuperKmers::new(&params, seq)
+                .unwrap()
+                .flat_map(|x| std::iter::repeat_n(x.key, x.end - x.start - 20))
+                .collect::<Vec<_>>()
+        };
+        let mut reverse = keys(&rc);
+        reverse.reverse();
+        assert_eq!(keys(&seq), reverse);
+
+        assert_eq!(SuperKmers::new(&params, &seq[..20]).unwrap().count(), 0);
+        let long = Params::new(41, 33, &[0]).unwrap();
+        assert!(SuperKmers::new(&long, &seq).is_err());
+    }
+
+    #[test]
+    pub fn test_superkmer_writer() {
+        let params = Params::new(21, 11, &[5]).unwrap().with_canonical(true);
+        let dir = std::env::temp_dir().join(format!("syncmers-superkmers-{}", std::process::id()));
+        let mut writer = SuperKmerWriter::create(params.clone(), &dir, 4).unwrap();
+        let seqs = [random_seq(3_000, 41), random_seq(2_000, 43)];
+        writer.write_sequence("a", &seqs[0]).unwrap();
+        writer.write_sequence("b", &seqs[1]).unwrap();
+        let paths = writer.finish().unwrap();
+        assert_eq!(paths.len(), 4);
+
+        let superkmers = seqs
+            .iter()
+            .map(|x| SuperKmers::new(&params, x).unwrap().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut records = 0;
+        for (i, path) in paths.iter().enumerate() {
+            for record in FastaReader::from_path(path).unwrap() {
+                let record = record.unwrap();
+                let (name, range) = record.id.split_once(':').unwrap();
+                let (start, end) = range.split_once('-').unwrap();
+                let (start, end) = (start.parse().unwrap(), end.parse::<usize>().unwrap());
+                let seq = (name == "b") as usize;
+                assert_eq!(record.seq, &seqs[seq][start..end]);
+                let key = superkmers[seq]
+                    .iter()
+                    .find(|x| x.start == start)
+                    .unwrap()
+                    .key;
+                assert_eq!(((key as u128 * 4) >> 64) as usize, i);
+                records += 1;
+            }
+        }
+        assert_eq!(records, superkmers.iter().map(|x| x.len()).sum::<usize>());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,8 @@
+//! Fixtures shared by the unit tests
+
+pub(crate) use crate::random::XorShift;
+
+/// Uniformly random ACGT sequence, the same for the same seed
+pub(crate) fn random_seq(len: usize, seed: u64) -> Vec<u8> {
+    crate::random::random_bases(len, seed)
+}
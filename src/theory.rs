@@ -0,0 +1,299 @@
+//! Expected density, spacing and conservation of syncmer parameters on random sequence
+//!
+//! Following Dutta et al., smers are modelled as ordered by independent random ranks, so the
+//! smallest smer of a kmer is equally likely to be at any of its w = k - s + 1 positions and
+//! the density is |ts| / w. Spacing and coverage come from an exact dynamic program over
+//! intervals of smers: the smallest smer of an interval decides every kmer containing it,
+//! and splits the rest into two independent intervals. The canonical flag of the params is
+//! ignored: this is the forward-strand scheme.
+//!
+//! [`Params::syncmers`] compares smers lexicographically with ties to the leftmost instead,
+//! and the two diverge in two places. Position 0 is favoured: about 1.2 / w of the kmers of
+//! random sequence select it, against slightly below 1 / w for the others, so t = 0 and closed
+//! syncmers are denser than |ts| / w. Canonical params favour small smers further, since the
+//! smaller of the kmer and its reverse complement is tested. [`calibrated_density`] gives the
+//! density of the real selection: the frequency of each offset is measured on a fixed
+//! pseudo-random sequence of [`CALIBRATION_KMERS`] kmers, once per k, s and canonical flag.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::random::random_bases;
+use crate::Params;
+
+/// Kmers of the pseudo-random sequence the calibration is measured on
+pub const CALIBRATION_KMERS: usize = 1 << 18;
+
+const CALIBRATION_SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+/// Calibrated offset frequencies, by k, s and canonical flag
+static CALIBRATIONS: Mutex<BTreeMap<(usize, usize, bool), Vec<f64>>> = Mutex::new(BTreeMap::new());
+
+/// Fraction of the calibration kmers selecting each smer offset
+fn measure_offset_frequencies(params: &Params) -> Vec<f64> {
+    let k = params.k();
+    let seq = random_bases(CALIBRATION_KMERS + k - 1, CALIBRATION_SEED);
+    let mut rc = vec![0; k];
+    let mut frequencies = vec![0.0; k - params.s() + 1];
+    for kmer in seq.windows(k) {
+        frequencies[params.select(kmer, &mut rc).2] += 1.0;
+    }
+    frequencies
+        .iter()
+        .map(|x| x / CALIBRATION_KMERS as f64)
+        .collect()
+}
+
+/// Fraction of kmers of random sequence whose smallest smer is at each offset, with the real
+/// selection of [`Params::syncmers`] (canonical kmers included). Measured on first use for
+/// each k, s and canonical flag, then cached
+pub fn calibrated_offset_frequencies(params: &Params) -> Vec<f64> {
+    let key = (params.k(), params.s(), params.canonical());
+    if let Some(frequencies) = CALIBRATIONS.lock().unwrap().get(&key) {
+        return frequencies.clone();
+    }
+    let frequencies = measure_offset_frequencies(params);
+    CALIBRATIONS
+        .lock()
+        .unwrap()
+        .entry(key)
+        .or_insert(frequencies)
+        .clone()
+}
+
+/// Expected fraction of kmers that are syncmers with the real (lexicographic) selection,
+/// unlike [`density`]
+pub fn calibrated_density(params: &Params) -> f64 {
+    let frequencies = calibrated_offset_frequencies(params);
+    params.ts().iter().map(|&t| frequencies[t]).sum()
+}
+
+/// Probabilities over intervals of L smers, for the syncmers of the kmers lying within them
+struct Intervals {
+    /// No kmer is a syncmer
+    none: Vec<f64>,
+    /// The first kmer is a syncmer and no other kmer is
+    first_only: Vec<f64>,
+}
+
+impl Intervals {
+    /// Both tables for intervals of up to max_len smers
+    fn new(params: &Params, max_len: usize) -> Self {
+        let w = params.k() - params.s() + 1;
+        // targets[i] = number of target positions below i
+        let mut targets = vec![0; w + 1];
+        for i in 0..w {
+            targets[i + 1] = targets[i] + params.ts().contains(&i) as usize;
+        }
+        let any_target = |lo: usize, hi: usize| lo <= hi && targets[hi + 1] > targets[lo];
+
+        let mut none = vec![1.0; max_len + 1];
+        let mut first_only = vec![0.0; max_len + 1];
+        for len in w..=max_len {
+            let (mut p_none, mut p_first) = (0.0, 0.0);
+            for m in 0..len {
+                let right = none[len - m - 1];
+                // Kmers containing smer m start from m - w + 1 to len - w, and all have it as
+                // their smallest smer, at offsets lo..=hi
+                let lo = (m + w).saturating_sub(len);
+                let hi = m.min(w - 1);
+                if !any_target(lo, hi) {
+                    p_none += none[m] * right;
+                }
+                if m < w {
+                    // The first kmer contains m and must be the only syncmer among them
+                    if params.ts().contains(&m) && (m == 0 || !any_target(lo, m - 1)) {
+                        p_first += right;
+                    }
+                } else if !any_target(lo, hi) {
+                    p_first += first_only[m] * right;
+                }
+            }
+            none[len] = p_none / len as f64;
+            first_only[len] = p_first / len as f64;
+        }
+        Intervals { none, first_only }
+    }
+}
+
+/// Expected fraction of kmers that are syncmers
+pub fn density(params: &Params) -> f64 {
+    params.ts().len() as f64 / (params.k() - params.s() + 1) as f64
+}
+
+/// Distribution of the distance between consecutive syncmers: element d is the probability
+/// that the next syncmer starts d bases after a syncmer, for d up to max_spacing (element 0
+/// is always 0). For open syncmers the distribution has no upper bound and the tail past
+/// max_spacing is left out
+pub fn spacing_distribution(params: &Params, max_spacing: usize) -> Vec<f64> {
+    let w = params.k() - params.s() + 1;
+    let intervals = Intervals::new(params, max_spacing + w);
+    // P(syncmer at 0 and none of the next d - 1 kmers), over the d + w - 1 smers of d kmers
+    let run = |d: usize| intervals.first_only[d + w - 1];
+    let mut spacing = vec![0.0; max_spacing + 1];
+    for (d, x) in spacing.iter_mut().enumerate().skip(1) {
+        *x = (run(d) - run(d + 1)) / run(1);
+    }
+    spacing
+}
+
+/// Probability that a syncmer is conserved (still found at the same place) after substitutions
+/// at the given rate: all of its k bases must be unchanged
+pub fn conservation(params: &Params, mutation_rate: f64) -> f64 {
+    (1.0 - mutation_rate).powi(params.k() as i32)
+}
+
+/// Expected fraction of bases covered by conserved syncmers after substitutions at the given
+/// rate, the conservation measure of Edgar and Dutta et al. With no mutations this is the
+/// fraction of bases covered by syncmers
+pub fn conserved_coverage(params: &Params, mutation_rate: f64) -> f64 {
+    let k = params.k();
+    let w = k - params.s() + 1;
+    let intervals = Intervals::new(params, k + w - 1);
+    let p = mutation_rate;
+    let q = 1.0 - p;
+
+    // u: distance back to the closest substitution at or before the base (k for none within
+    // k - 1), v: distance to the next one after it (k for none within k - 1)
+    let before = |u: usize| {
+        if u < k {
+            q.powi(u as i32) * p
+        } else {
+            q.powi(k as i32)
+        }
+    };
+    let after = |v: usize| {
+        if v < k {
+            q.powi(v as i32 - 1) * p
+        } else {
+            q.powi(k as i32 - 1)
+        }
+    };
+    let mut covered = 0.0;
+    for u in 1..=k {
+        for v in 1..=k {
+            // Unmutated kmers covering the base, which are consecutive
+            let clean = u.saturating_sub(k - v);
+            if clean > 0 {
+                covered += before(u) * after(v) * (1.0 - intervals.none[clean + w - 1]);
+            }
+        }
+    }
+    covered
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::evaluate::mutate;
+    use crate::find_syncmers_pos;
+    use crate::test_util::random_seq;
+
+    #[test]
+    pub fn test_density_and_spacing() {
+        let params = Params::new(21, 11, &[3, 7]).unwrap();
+        let seq = random_seq(500_000, 83);
+        let positions = find_syncmers_pos(21, 11, &[3, 7], &seq);
+        let measured = positions.len() as f64 / (seq.len() - 20) as f64;
+        assert_eq!(density(&params), 2.0 / 11.0);
+        assert!((measured / density(&params) - 1.0).abs() < 0.05);
+
+        let spacing = spacing_distribution(&params, 200);
+        let total = spacing.iter().sum::<f64>();
+        assert!(total > 0.999 && total < 1.0 + 1e-9);
+        let mean = spacing
+            .iter()
+            .enumerate()
+            .map(|(d, x)| d as f64 * x)
+            .sum::<f64>();
+        assert!((mean * density(&params) - 1.0).abs() < 1e-3);
+
+        let mut observed = vec![0.0; 201];
+        for pair in positions.windows(2) {
+            observed[(pair[1] - pair[0]).min(200)] += 1.0 / (positions.len() - 1) as f64;
+        }
+        let distance = spacing
+            .iter()
+            .zip(&observed)
+            .map(|(a, b)| (a - b).abs())
+            .sum::<f64>();
+        assert!(distance < 0.1, "total variation {}", distance / 2.0);
+    }
+
+    #[test]
+    pub fn test_closed_syncmer_spacing() {
+        // Among any w - 1 consecutive kmers, the smallest smer of their 2w - 2 smers either
+        // starts one of them or ends one of them, so syncmers are at most w - 1 apart
+        let params = Params::closed(12, 4).unwrap();
+        let spacing = spacing_distribution(&params, 100);
+        let w = 9;
+        assert!((density(&params) - 2.0 / w as f64).abs() < 1e-12);
+        assert!(spacing[w - 1] > 0.0 && spacing[w..].iter().all(|&x| x < 1e-12));
+        assert!((spacing.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    pub fn test_conserved_coverage() {
+        let (k, s, ts) = (15, 7, [4]);
+        let params = Params::new(k, s, &ts).unwrap();
+        let seq = random_seq(300_000, 89);
+        for rate in [0.0, 0.02, 0.1] {
+            let mutated = mutate(&seq, rate, 97);
+            let original = find_syncmers_pos(k, s, &ts, &seq);
+            let conserved = original
+                .iter()
+                .filter(|&&i| seq[i..i + k] == mutated[i..i + k])
+                .collect::<Vec<_>>();
+            let fraction = conserved.len() as f64 / original.len() as f64;
+            assert!((fraction - conservation(&params, rate)).abs() < 0.02);
+
+            let mut covered = vec![false; seq.len()];
+            for &&i in &conserved {
+                covered[i..i + k].iter_mut().for_each(|x| *x = true);
+            }
+            let measured = covered.iter().filter(|&&x| x).count() as f64 / seq.len() as f64;
+            let expected = conserved_coverage(&params, rate);
+            assert!(
+                (measured - expected).abs() < 0.03,
+                "{} vs {}",
+                measured,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_calibrated_density() {
+        // t = 0 and closed syncmers are where lexicographic order departs from |ts| / w
+        let seq = random_seq(500_000, 83);
+        for (ts, positions) in [
+            (&[0][..], find_syncmers_pos(21, 11, &[0], &seq)),
+            (&[0, 10], find_syncmers_pos(21, 11, &[0, 10], &seq)),
+            (&[5], find_syncmers_pos(21, 11, &[5], &seq)),
+        ] {
+            let params = Params::new(21, 11, ts).unwrap();
+            let measured = positions.len() as f64 / (seq.len() - 20) as f64;
+            let expected = calibrated_density(&params);
+            assert!(
+                (measured / expected - 1.0).abs() < 0.02,
+                "{:?}: {} vs {}",
+                ts,
+                measured,
+                expected
+            );
+        }
+        let open = Params::new(21, 11, &[0]).unwrap();
+        assert!(calibrated_density(&open) > 1.15 * density(&open));
+        let frequencies = calibrated_offset_frequencies(&open);
+        assert!((frequencies.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert_eq!(calibrated_offset_frequencies(&open), frequencies);
+
+        // Canonical kmers favour small smers further
+        let canonical = Params::new(15, 7, &[0]).unwrap().with_canonical(true);
+        let measured = canonical.syncmers(&seq).len() as f64 / (seq.len() - 14) as f64;
+        assert!((measured / calibrated_density(&canonical) - 1.0).abs() < 0.02);
+        assert!(
+            calibrated_density(&canonical) > calibrated_density(&canonical.with_canonical(false))
+        );
+    }
+}
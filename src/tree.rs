@@ -0,0 +1,269 @@
+//! Neighbor-joining and UPGMA trees from syncmer distance matrices, written as Newick
+//!
+//! Together with [`crate::dist`] this gives an alignment-free phylogeny straight from FASTA.
+
+use crate::dist::DistanceMatrix;
+
+/// Tree building method
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeMethod {
+    /// Neighbor joining (Saitou & Nei 1987). Unrooted, written with a basal trifurcation
+    NeighborJoining,
+    /// Average linkage clustering. Rooted and ultrametric
+    Upgma,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    label: Option<String>,
+    // (child, branch length)
+    children: Vec<(usize, f64)>,
+}
+
+/// A tree whose leaves are labelled with the matrix labels
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tree {
+    nodes: Vec<Node>,
+    root: usize,
+}
+
+impl Tree {
+    /// Build a tree from a distance matrix
+    ///
+    /// ```rust
+    /// # use syncmers::dist::DistanceMatrix;
+    /// # use syncmers::tree::{Tree, TreeMethod};
+    /// let labels = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+    /// let matrix = DistanceMatrix::new(labels, vec![0.0, 0.2, 0.6, 0.2, 0.0, 0.6, 0.6, 0.6, 0.0]);
+    /// let tree = Tree::build(&matrix, TreeMethod::Upgma);
+    /// assert_eq!(tree.to_newick(), "(c:0.3,(a:0.1,b:0.1):0.2);");
+    /// ```
+    pub fn build(matrix: &DistanceMatrix, method: TreeMethod) -> Self {
+        assert!(!matrix.is_empty());
+        match method {
+            TreeMethod::NeighborJoining => neighbor_joining(matrix),
+            TreeMethod::Upgma => upgma(matrix),
+        }
+    }
+
+    /// Number of leaves
+    pub fn leaves(&self) -> usize {
+        self.nodes.iter().filter(|x| x.children.is_empty()).count()
+    }
+
+    /// Newick string, terminated with ';'
+    pub fn to_newick(&self) -> String {
+        let mut out = String::new();
+        self.write_node(self.root, &mut out);
+        out.push(';');
+        out
+    }
+
+    fn write_node(&self, node: usize, out: &mut String) {
+        let node = &self.nodes[node];
+        if !node.children.is_empty() {
+            out.push('(');
+            for (i, &(child, length)) in node.children.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                self.write_node(child, out);
+                out.push(':');
+                out.push_str(&format_length(length));
+            }
+            out.push(')');
+        }
+        if let Some(label) = &node.label {
+            out.push_str(&quote_label(label));
+        }
+    }
+}
+
+fn format_length(length: f64) -> String {
+    // Trim trailing zeroes so simple distances stay readable
+    let s = format!("{:.8}", length);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s.is_empty() || s == "-" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+/// Quote labels containing Newick metacharacters
+fn quote_label(label: &str) -> String {
+    if label
+        .chars()
+        .any(|c| c.is_whitespace() || "()[]':;,".contains(c))
+    {
+        format!("'{}'", label.replace('\'', "''"))
+    } else {
+        label.to_string()
+    }
+}
+
+fn leaves(matrix: &DistanceMatrix) -> Vec<Node> {
+    matrix
+        .labels()
+        .iter()
+        .map(|label| Node {
+            label: Some(label.clone()),
+            children: Vec::new(),
+        })
+        .collect()
+}
+
+/// Pair (i < j) of the m active clusters with the lowest score
+fn best_pair<F: Fn(usize, usize) -> f64>(m: usize, score: F) -> (usize, usize) {
+    let mut best = (0, 1, f64::INFINITY);
+    for i in 0..m {
+        for j in i + 1..m {
+            let q = score(i, j);
+            if q < best.2 {
+                best = (i, j, q);
+            }
+        }
+    }
+    (best.0, best.1)
+}
+
+/// Replace clusters i and j (i < j) with a new cluster, appended last, whose distances to the
+/// remaining clusters are new_row
+fn join(d: &mut Vec<Vec<f64>>, i: usize, j: usize, new_row: Vec<f64>) {
+    for row in d.iter_mut() {
+        row.remove(j);
+        row.remove(i);
+    }
+    d.remove(j);
+    d.remove(i);
+
+    for (row, &x) in d.iter_mut().zip(new_row.iter()) {
+        row.push(x);
+    }
+    let mut last = new_row;
+    last.push(0.0);
+    d.push(last);
+}
+
+fn neighbor_joining(matrix: &DistanceMatrix) -> Tree {
+    let n = matrix.len();
+    let mut nodes = leaves(matrix);
+    if n == 1 {
+        return Tree { nodes, root: 0 };
+    }
+
+    // Working distances between active clusters, indexed by position in `active`
+    let mut d = (0..n).map(|i| matrix.row(i).to_vec()).collect::<Vec<_>>();
+    let mut active = (0..n).collect::<Vec<usize>>();
+
+    while active.len() > 3 {
+        let m = active.len();
+        let sums = d
+            .iter()
+            .map(|row| row.iter().sum::<f64>())
+            .collect::<Vec<_>>();
+
+        let (i, j) = best_pair(m, |i, j| (m - 2) as f64 * d[i][j] - sums[i] - sums[j]);
+
+        let li = 0.5 * d[i][j] + (sums[i] - sums[j]) / (2.0 * (m - 2) as f64);
+        let lj = d[i][j] - li;
+        nodes.push(Node {
+            label: None,
+            children: vec![(active[i], li.max(0.0)), (active[j], lj.max(0.0))],
+        });
+
+        let new_row = (0..m)
+            .filter(|&x| x != i && x != j)
+            .map(|x| 0.5 * (d[i][x] + d[j][x] - d[i][j]))
+            .collect::<Vec<_>>();
+
+        join(&mut d, i, j, new_row);
+        active.remove(j);
+        active.remove(i);
+        active.push(nodes.len() - 1);
+    }
+
+    let children = if active.len() == 3 {
+        let (a, b, c) = (d[0][1], d[0][2], d[1][2]);
+        vec![
+            (active[0], (0.5 * (a + b - c)).max(0.0)),
+            (active[1], (0.5 * (a + c - b)).max(0.0)),
+            (active[2], (0.5 * (b + c - a)).max(0.0)),
+        ]
+    } else {
+        vec![(active[0], d[0][1] / 2.0), (active[1], d[0][1] / 2.0)]
+    };
+    nodes.push(Node {
+        label: None,
+        children,
+    });
+    let root = nodes.len() - 1;
+    Tree { nodes, root }
+}
+
+fn upgma(matrix: &DistanceMatrix) -> Tree {
+    let n = matrix.len();
+    let mut nodes = leaves(matrix);
+    let mut d = (0..n).map(|i| matrix.row(i).to_vec()).collect::<Vec<_>>();
+    // (node, number of leaves, height)
+    let mut active = (0..n).map(|i| (i, 1usize, 0.0f64)).collect::<Vec<_>>();
+
+    while active.len() > 1 {
+        let m = active.len();
+        let (i, j) = best_pair(m, |i, j| d[i][j]);
+        let dij = d[i][j];
+        let (ni, si, hi) = active[i];
+        let (nj, sj, hj) = active[j];
+        let height = dij / 2.0;
+        nodes.push(Node {
+            label: None,
+            children: vec![(ni, (height - hi).max(0.0)), (nj, (height - hj).max(0.0))],
+        });
+
+        let new_row = (0..m)
+            .filter(|&x| x != i && x != j)
+            .map(|x| (d[i][x] * si as f64 + d[j][x] * sj as f64) / (si + sj) as f64)
+            .collect::<Vec<_>>();
+
+        join(&mut d, i, j, new_row);
+        active.remove(j);
+        active.remove(i);
+        active.push((nodes.len() - 1, si + sj, height));
+    }
+
+    let root = active[0].0;
+    Tree { nodes, root }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_neighbor_joining_additive() {
+        // Additive distances from the tree ((a:1,b:2):1,c:3,(d:1,e:1):2)
+        let labels = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+        #[rustfmt::skip]
+        let distances = vec![
+            0.0, 3.0, 5.0, 5.0, 5.0,
+            3.0, 0.0, 6.0, 6.0, 6.0,
+            5.0, 6.0, 0.0, 6.0, 6.0,
+            5.0, 6.0, 6.0, 0.0, 2.0,
+            5.0, 6.0, 6.0, 2.0, 0.0,
+        ];
+        let matrix = DistanceMatrix::new(labels, distances);
+        let tree = Tree::build(&matrix, TreeMethod::NeighborJoining);
+        assert_eq!(tree.leaves(), 5);
+        assert_eq!(tree.to_newick(), "(c:3,(d:1,e:1):2,(a:1,b:2):1);");
+    }
+
+    #[test]
+    pub fn test_labels_quoted() {
+        assert_eq!(quote_label("E. coli"), "'E. coli'");
+        assert_eq!(quote_label("it's"), "'it''s'");
+        assert_eq!(quote_label("genome_1.fasta"), "genome_1.fasta");
+    }
+}
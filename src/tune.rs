@@ -0,0 +1,237 @@
+//! Choosing s and target positions for a kmer length and a desired density
+//!
+//! Every s and target set whose density is within tolerance of the target is kept, the
+//! density being [`theory::calibrated_density`](crate::theory::calibrated_density) as the real
+//! selection departs from |ts| / w. Candidates are scored with the model of
+//! [`theory`](crate::theory): by the fraction of bases covered by conserved syncmers at a
+//! substitution rate, then by how evenly syncmers are spaced, then by whether their spacing is
+//! bounded.
+
+use std::cmp::Ordering;
+
+use rayon::prelude::*;
+
+use crate::theory::{
+    calibrated_density, calibrated_offset_frequencies, conserved_coverage, density,
+    spacing_distribution,
+};
+use crate::{Error, Params};
+
+/// Search parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuneParams {
+    /// Accepted deviation from the target density, relative to it
+    pub tolerance: f64,
+    /// Substitution rate conservation is scored at
+    pub mutation_rate: f64,
+    /// Largest number of target positions tried (find_syncmers_pos takes at most 4)
+    pub max_targets: usize,
+    /// Shortest smer tried
+    pub min_s: usize,
+}
+
+impl Default for TuneParams {
+    fn default() -> Self {
+        TuneParams {
+            tolerance: 0.1,
+            mutation_rate: 0.05,
+            max_targets: 4,
+            min_s: 5,
+        }
+    }
+}
+
+/// A scored parameter set
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub params: Params,
+    pub density: f64,
+    /// Fraction of bases covered by conserved syncmers
+    pub conserved_coverage: f64,
+    /// Standard deviation of the distance between consecutive syncmers
+    pub spacing_sd: f64,
+    /// Longest possible distance between consecutive syncmers: w - 1 (w = k - s + 1) when the
+    /// targets include 0 and w - 1 as closed syncmers do, None (unbounded) otherwise
+    pub max_spacing: Option<usize>,
+}
+
+/// Subsets of size n of 0..w, in lexicographic order
+fn combinations(w: usize, n: usize) -> Vec<Vec<usize>> {
+    let mut combinations = Vec::new();
+    let mut current = Vec::with_capacity(n);
+    fn extend(
+        start: usize,
+        w: usize,
+        n: usize,
+        current: &mut Vec<usize>,
+        out: &mut Vec<Vec<usize>>,
+    ) {
+        if current.len() == n {
+            out.push(current.clone());
+            return;
+        }
+        for i in start..=w - (n - current.len()) {
+            current.push(i);
+            extend(i + 1, w, n, current, out);
+            current.pop();
+        }
+    }
+    extend(0, w, n, &mut current, &mut combinations);
+    combinations
+}
+
+fn score(params: Params, tune_params: &TuneParams) -> Candidate {
+    // Far enough that the tail of unbounded spacings is negligible
+    let horizon = (20.0 / density(&params)).ceil() as usize;
+    let spacing = spacing_distribution(&params, horizon);
+    let total = spacing.iter().sum::<f64>();
+    let mean = spacing
+        .iter()
+        .enumerate()
+        .map(|(d, x)| d as f64 * x)
+        .sum::<f64>()
+        / total;
+    let variance = spacing
+        .iter()
+        .enumerate()
+        .map(|(d, x)| (d as f64 - mean).powi(2) * x)
+        .sum::<f64>()
+        / total;
+    // Any w - 1 consecutive kmers include the start or the end of their smallest smer, and
+    // without either target runs of increasing or decreasing smers have no syncmer
+    let w = params.k() - params.s() + 1;
+    let ts = params.ts();
+    let max_spacing = (ts.contains(&0) && ts.contains(&(w - 1))).then_some(w - 1);
+
+    Candidate {
+        density: calibrated_density(&params),
+        conserved_coverage: conserved_coverage(&params, tune_params.mutation_rate),
+        spacing_sd: variance.sqrt(),
+        max_spacing,
+        params,
+    }
+}
+
+/// Ranking: more conserved coverage first, then more even spacing, then bounded spacing
+fn compare(a: &Candidate, b: &Candidate) -> Ordering {
+    b.conserved_coverage
+        .partial_cmp(&a.conserved_coverage)
+        .unwrap_or(Ordering::Equal)
+        .then(
+            a.spacing_sd
+                .partial_cmp(&b.spacing_sd)
+                .unwrap_or(Ordering::Equal),
+        )
+        .then(match (a.max_spacing, b.max_spacing) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        })
+}
+
+/// All parameter sets for kmers of length k with an expected density within tolerance of the
+/// target, best first
+///
+/// ```rust
+/// # use syncmers::tune::{tune, TuneParams};
+/// let candidates = tune(15, 0.2, &TuneParams::default()).unwrap();
+/// let best = &candidates[0];
+/// assert!((best.density - 0.2).abs() <= 0.02);
+/// ```
+pub fn tune(k: usize, target: f64, tune_params: &TuneParams) -> Result<Vec<Candidate>, Error> {
+    if !(target > 0.0 && target <= 1.0) {
+        return Err(Error::InvalidParams(format!(
+            "Density must be in (0, 1], got {}",
+            target
+        )));
+    }
+
+    let (low, high) = (
+        target * (1.0 - tune_params.tolerance),
+        target * (1.0 + tune_params.tolerance),
+    );
+    // The calibrated offset frequencies of each s, then the target sets within tolerance
+    let mut sets = Vec::new();
+    for s in tune_params.min_s.max(1)..k {
+        let frequencies = calibrated_offset_frequencies(&Params::new(k, s, &[0]).unwrap());
+        let (min, max) = frequencies
+            .iter()
+            .fold((f64::MAX, 0.0f64), |(a, b), &x| (a.min(x), b.max(x)));
+        let w = k - s + 1;
+        for n in 1..=tune_params.max_targets.min(w) {
+            if n as f64 * min > high || (n as f64 * max) < low {
+                continue;
+            }
+            for ts in combinations(w, n) {
+                let density = ts.iter().map(|&t| frequencies[t]).sum::<f64>();
+                if density >= low && density <= high {
+                    sets.push(Params::new(k, s, &ts).unwrap());
+                }
+            }
+        }
+    }
+
+    let mut candidates = sets
+        .into_par_iter()
+        .map(|params| score(params, tune_params))
+        .collect::<Vec<_>>();
+    candidates.sort_by(compare);
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::theory;
+
+    #[test]
+    pub fn test_combinations() {
+        assert_eq!(combinations(3, 2), vec![vec![0, 1], vec![0, 2], vec![1, 2]]);
+        assert_eq!(combinations(10, 3).len(), 120);
+        assert_eq!(combinations(4, 4), vec![vec![0, 1, 2, 3]]);
+    }
+
+    #[test]
+    pub fn test_tune() {
+        let candidates = tune(21, 0.1, &TuneParams::default()).unwrap();
+        assert!(!candidates.is_empty());
+        for x in &candidates {
+            assert!((x.density - 0.1).abs() <= 0.01 && x.params.s() >= 5);
+            // Densities are those of the real selection
+            assert!((x.density / theory::calibrated_density(&x.params) - 1.0).abs() < 1e-9);
+        }
+        // t = 0 is selected well above 1 / w: 0.11 for s = 11 and 0.12 for s = 12
+        let find = |ts: &[usize], s: usize| {
+            candidates
+                .iter()
+                .position(|x| x.params.ts() == ts && x.params.s() == s)
+        };
+        assert!(find(&[0], 11).is_none() && find(&[0], 12).is_none());
+        assert!(find(&[4], 12).is_some());
+        assert!(candidates
+            .windows(2)
+            .all(|x| x[0].conserved_coverage >= x[1].conserved_coverage));
+        let best = &candidates[0];
+        assert!(best.conserved_coverage > 0.0 && best.spacing_sd > 0.0);
+
+        // Only closed syncmers have bounded spacing, k - s
+        let closed = tune(
+            12,
+            0.22,
+            &TuneParams {
+                min_s: 4,
+                tolerance: 0.2,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let bounded = closed.iter().find(|x| x.params.ts() == [0, 8]).unwrap();
+        assert_eq!(bounded.max_spacing, Some(8));
+        assert!(closed
+            .iter()
+            .all(|x| x.params.ts() == [0, 8] || x.max_spacing.is_none()));
+
+        assert!(tune(21, 0.0, &TuneParams::default()).is_err());
+    }
+}